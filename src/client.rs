@@ -14,6 +14,28 @@ pub use hyper_tls::HttpsConnector as NativeTlsConnector;
 use hyper_util::client::legacy::connect::Connect;
 pub use hyper_util::client::legacy::connect::HttpConnector;
 pub use hyper_util::client::legacy::{Builder, Client};
+#[cfg(feature = "proxy-chain")]
+#[cfg_attr(docsrs, doc(cfg(feature = "proxy-chain")))]
+pub use proxy::{HttpProxyConnector, Socks5Connector};
+#[cfg(feature = "nativetls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "nativetls")))]
+pub use tls::NativeTlsClientBuilder;
+#[cfg(feature = "__rustls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+pub use tls::RustlsClientBuilder;
+#[cfg(any(feature = "nativetls", feature = "__rustls"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "nativetls", feature = "rustls"))))]
+pub use tls::TlsError;
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub use unix::{UnixConnector, UnixStream};
+
+#[cfg(feature = "proxy-chain")]
+mod proxy;
+#[cfg(any(feature = "nativetls", feature = "__rustls"))]
+mod tls;
+#[cfg(unix)]
+mod unix;
 
 /// Default [`Builder`].
 #[must_use]
@@ -31,6 +53,35 @@ where
     Builder::new(hyper_util::rt::TokioExecutor::new()).build_http()
 }
 
+/// Speaks HTTP/2 over plain TCP with prior knowledge (h2c), whatever the version of the requests.
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+#[must_use]
+pub fn h2c_default<B>() -> Client<HttpConnector, B>
+where
+    B: HttpBody + Send,
+    B::Data: Send,
+{
+    Builder::new(hyper_util::rt::TokioExecutor::new())
+        .http2_only(true)
+        .build_http()
+}
+
+/// With a [`UnixConnector`] to the socket at `path`.
+///
+/// `path` may be prefixed with `unix://`.
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+#[must_use]
+pub fn unix_default<B, P>(path: P) -> Client<UnixConnector, B>
+where
+    B: HttpBody + Send,
+    B::Data: Send,
+    P: AsRef<std::path::Path>,
+{
+    Builder::new(hyper_util::rt::TokioExecutor::new()).build(UnixConnector::new(path))
+}
+
 /// Alias to [`nativetls_default()`].
 #[cfg(any(feature = "https", feature = "nativetls"))]
 #[cfg_attr(docsrs, doc(cfg(any(feature = "https", feature = "nativetls"))))]
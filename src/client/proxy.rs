@@ -0,0 +1,516 @@
+use std::fmt;
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::uri::{Authority, Uri};
+use http::Error as HttpError;
+use hyper::rt::{Read, Write};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tower_service::Service;
+
+use super::HttpConnector;
+
+type BoxErr = Box<dyn std::error::Error + Send + Sync>;
+type ConnectFuture<T> = Pin<Box<dyn Future<Output = Result<T, BoxErr>> + Send>>;
+
+/// The longest response head accepted from an HTTP proxy.
+const MAX_HEAD: usize = 8 * 1024;
+
+/// Printed by `Debug` in place of the credentials.
+const REDACTED: &str = "<redacted>";
+
+/// The host and the port of the target, with the default port of the scheme.
+fn target(uri: &Uri) -> Result<(&str, u16), IoError> {
+    let host = uri
+        .host()
+        .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "no host in the target"))?;
+    let default_port = if uri.scheme_str() == Some("https") {
+        443
+    } else {
+        80
+    };
+    Ok((host, uri.port_u16().unwrap_or(default_port)))
+}
+
+/// The URI to connect `inner` to the proxy at `authority`.
+fn proxy_uri(authority: &Authority, default_port: u16) -> Uri {
+    let port = authority.port_u16().unwrap_or(default_port);
+    let uri = format!("http://{}:{port}", authority.host());
+    Uri::try_from(uri).expect("an authority with a port is a valid URI")
+}
+
+/// A connector tunnelling connections through an HTTP proxy, by `CONNECT` requests.
+///
+/// The connections to the proxy are made by the inner connector `C`. For `https` upstreams, wrap
+/// this with a TLS connector, *e.g.* by
+/// [`RustlsClientBuilder::build_with_connector()`](super::RustlsClientBuilder::build_with_connector):
+///
+/// ```no_run
+/// # #[cfg(feature = "__rustls")] {
+/// use axum_proxy::client::{HttpProxyConnector, RustlsClientBuilder};
+/// use hyper::body::Incoming;
+///
+/// let proxy = HttpProxyConnector::new("proxy.corp:3128")
+///     .unwrap()
+///     .basic_auth("user", "password");
+/// let client = RustlsClientBuilder::new()
+///     .build_with_connector::<_, Incoming>(proxy)
+///     .unwrap();
+/// let svc_builder = axum_proxy::builder(client, "https", "example.com").unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HttpProxyConnector<C = HttpConnector> {
+    inner: C,
+    proxy: Uri,
+    authorization: Option<String>,
+}
+
+/// Redacts the credentials.
+impl<C: fmt::Debug> fmt::Debug for HttpProxyConnector<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpProxyConnector")
+            .field("inner", &self.inner)
+            .field("proxy", &self.proxy)
+            .field(
+                "authorization",
+                &self.authorization.as_ref().map(|_| REDACTED),
+            )
+            .finish()
+    }
+}
+
+impl HttpProxyConnector {
+    /// Connects to the proxy at `proxy`, *e.g.* `proxy.corp:3128`, with an [`HttpConnector`].
+    ///
+    /// # Errors
+    ///
+    /// When `proxy` cannot be converted into an [`Authority`].
+    pub fn new<A>(proxy: A) -> Result<Self, HttpError>
+    where
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<HttpError>,
+    {
+        Self::with_connector(HttpConnector::new(), proxy)
+    }
+}
+
+impl<C> HttpProxyConnector<C> {
+    /// Connects to the proxy at `proxy` with `inner`, on the port `80` if omitted.
+    ///
+    /// # Errors
+    ///
+    /// When `proxy` cannot be converted into an [`Authority`].
+    pub fn with_connector<A>(inner: C, proxy: A) -> Result<Self, HttpError>
+    where
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<HttpError>,
+    {
+        let proxy = proxy.try_into().map_err(Into::into)?;
+        Ok(Self {
+            inner,
+            proxy: proxy_uri(&proxy, 80),
+            authorization: None,
+        })
+    }
+
+    /// Authenticates to the proxy by the `Proxy-Authorization: Basic` header.
+    #[must_use]
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        let credentials = STANDARD.encode(format!("{username}:{password}"));
+        self.authorization = Some(format!("Basic {credentials}"));
+        self
+    }
+}
+
+impl<C> Service<Uri> for HttpProxyConnector<C>
+where
+    C: Service<Uri> + Send,
+    C::Response: Read + Write + Unpin + Send + 'static,
+    C::Future: Send + 'static,
+    C::Error: Into<BoxErr>,
+{
+    type Response = C::Response;
+    type Error = BoxErr;
+    type Future = ConnectFuture<C::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting = self.inner.call(self.proxy.clone());
+        let authorization = self.authorization.clone();
+        Box::pin(async move {
+            let (host, port) = target(&dst)?;
+            let mut io = TokioIo::new(connecting.await.map_err(Into::into)?);
+
+            let mut head = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+            if let Some(authorization) = authorization {
+                head.push_str(&format!("Proxy-Authorization: {authorization}\r\n"));
+            }
+            head.push_str("\r\n");
+            io.write_all(head.as_bytes()).await?;
+
+            let status = read_head(&mut io).await?;
+            let ok = status
+                .split(' ')
+                .nth(1)
+                .is_some_and(|code| code.starts_with('2'));
+            if !ok {
+                return Err(format!("The proxy responded with {status}").into());
+            }
+            Ok(io.into_inner())
+        })
+    }
+}
+
+/// Reads the response head from an HTTP proxy, returning the status line.
+///
+/// The head is read byte by byte, so that nothing after it is consumed.
+async fn read_head<T: AsyncRead + Unpin>(io: &mut T) -> Result<String, IoError> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD {
+            return Err(IoError::new(
+                ErrorKind::InvalidData,
+                "too long proxy response",
+            ));
+        }
+        head.push(io.read_u8().await?);
+    }
+    let status = head.split(|&b| b == b'\r').next().unwrap_or_default();
+    Ok(String::from_utf8_lossy(status).into_owned())
+}
+
+/// A connector tunnelling connections through a SOCKS5 proxy.
+///
+/// The connections to the proxy are made by the inner connector `C`. The host names are resolved
+/// by the proxy. Like [`HttpProxyConnector`], this can be wrapped with a TLS connector.
+#[derive(Clone)]
+pub struct Socks5Connector<C = HttpConnector> {
+    inner: C,
+    proxy: Uri,
+    credentials: Option<(String, String)>,
+}
+
+/// Redacts the credentials.
+impl<C: fmt::Debug> fmt::Debug for Socks5Connector<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Socks5Connector")
+            .field("inner", &self.inner)
+            .field("proxy", &self.proxy)
+            .field("credentials", &self.credentials.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+impl Socks5Connector {
+    /// Connects to the proxy at `proxy`, *e.g.* `proxy.corp:1080`, with an [`HttpConnector`].
+    ///
+    /// # Errors
+    ///
+    /// When `proxy` cannot be converted into an [`Authority`].
+    pub fn new<A>(proxy: A) -> Result<Self, HttpError>
+    where
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<HttpError>,
+    {
+        Self::with_connector(HttpConnector::new(), proxy)
+    }
+}
+
+impl<C> Socks5Connector<C> {
+    /// Connects to the proxy at `proxy` with `inner`, on the port `1080` if omitted.
+    ///
+    /// # Errors
+    ///
+    /// When `proxy` cannot be converted into an [`Authority`].
+    pub fn with_connector<A>(inner: C, proxy: A) -> Result<Self, HttpError>
+    where
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<HttpError>,
+    {
+        let proxy = proxy.try_into().map_err(Into::into)?;
+        Ok(Self {
+            inner,
+            proxy: proxy_uri(&proxy, 1080),
+            credentials: None,
+        })
+    }
+
+    /// Authenticates to the proxy by the username and the password.
+    #[must_use]
+    pub fn auth(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_owned(), password.to_owned()));
+        self
+    }
+}
+
+impl<C> Service<Uri> for Socks5Connector<C>
+where
+    C: Service<Uri> + Send,
+    C::Response: Read + Write + Unpin + Send + 'static,
+    C::Future: Send + 'static,
+    C::Error: Into<BoxErr>,
+{
+    type Response = C::Response;
+    type Error = BoxErr;
+    type Future = ConnectFuture<C::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting = self.inner.call(self.proxy.clone());
+        let credentials = self.credentials.clone();
+        Box::pin(async move {
+            let (host, port) = target(&dst)?;
+            let mut io = TokioIo::new(connecting.await.map_err(Into::into)?);
+            socks5_handshake(&mut io, host, port, credentials.as_ref()).await?;
+            Ok(io.into_inner())
+        })
+    }
+}
+
+/// Performs the SOCKS5 handshake of RFC 1928 and RFC 1929.
+async fn socks5_handshake<T: AsyncRead + AsyncWrite + Unpin>(
+    io: &mut T,
+    host: &str,
+    port: u16,
+    credentials: Option<&(String, String)>,
+) -> Result<(), BoxErr> {
+    const NO_AUTH: u8 = 0x00;
+    const USERNAME_PASSWORD: u8 = 0x02;
+
+    let method = if credentials.is_some() {
+        USERNAME_PASSWORD
+    } else {
+        NO_AUTH
+    };
+    io.write_all(&[5, 1, method]).await?;
+    let mut reply = [0; 2];
+    io.read_exact(&mut reply).await?;
+    if reply != [5, method] {
+        return Err("The SOCKS5 proxy rejected the authentication method".into());
+    }
+
+    if let Some((username, password)) = credentials {
+        let mut request = vec![1];
+        for field in [username, password] {
+            let len = u8::try_from(field.len()).map_err(|_| "Too long SOCKS5 credentials")?;
+            request.push(len);
+            request.extend_from_slice(field.as_bytes());
+        }
+        io.write_all(&request).await?;
+        io.read_exact(&mut reply).await?;
+        if reply[0] != 1 {
+            return Err("Invalid SOCKS5 authentication reply".into());
+        }
+        if reply[1] != 0 {
+            return Err("The SOCKS5 proxy rejected the credentials".into());
+        }
+    }
+
+    let mut request = vec![5, 1, 0];
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        },
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        },
+        Err(_) => {
+            let len = u8::try_from(host.len()).map_err(|_| "Too long host name for SOCKS5")?;
+            request.extend_from_slice(&[3, len]);
+            request.extend_from_slice(host.as_bytes());
+        },
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    io.write_all(&request).await?;
+
+    let mut reply = [0; 4];
+    io.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(format!(
+            "The SOCKS5 proxy failed to connect, with the code {}",
+            reply[1]
+        )
+        .into());
+    }
+    let address_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => usize::from(io.read_u8().await?),
+        _ => return Err("Invalid SOCKS5 reply".into()),
+    };
+    let mut bound = vec![0; address_len + 2];
+    io.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Bytes;
+    use hyper_util::client::legacy::Client;
+    use hyper_util::rt::TokioExecutor;
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    /// Spawns a proxy, which handles the handshake by `handshake` and then splices.
+    async fn spawn_proxy<F, Fut>(handshake: F) -> String
+    where
+        F: FnOnce(TcpStream) -> Fut + Send + 'static,
+        Fut: Future<Output = (TcpStream, TcpStream)> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (mut client, mut upstream) = handshake(stream).await;
+            tokio::io::copy_bidirectional(&mut client, &mut upstream)
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    async fn get<C>(connector: C, server: &mockito::ServerGuard) -> Bytes
+    where
+        C: Service<Uri> + Clone + Send + Sync + 'static,
+        C::Response: hyper_util::client::legacy::connect::Connection + Read + Write,
+        C::Response: Unpin + Send,
+        C::Future: Unpin + Send,
+        C::Error: Into<BoxErr>,
+    {
+        let client = Client::builder(TokioExecutor::new()).build::<_, Empty<Bytes>>(connector);
+        let uri = format!("{}/foo", server.url()).parse().unwrap();
+        let response = client.get(uri).await.unwrap();
+        response.into_body().collect().await.unwrap().to_bytes()
+    }
+
+    #[test]
+    fn redacted() {
+        let http = HttpProxyConnector::new("proxy:3128")
+            .unwrap()
+            .basic_auth("user", "secret");
+        let socks5 = Socks5Connector::new("proxy:1080")
+            .unwrap()
+            .auth("user", "secret");
+        for debug in [format!("{http:?}"), format!("{socks5:?}")] {
+            assert!(debug.contains(REDACTED), "{debug}");
+            assert!(
+                !debug.contains("user") && !debug.contains("secret"),
+                "{debug}"
+            );
+            assert!(!debug.contains(&STANDARD.encode("user:secret")), "{debug}");
+        }
+    }
+
+    #[tokio::test]
+    async fn http_proxy() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("GET", "/foo")
+            .with_body("ok")
+            .create_async()
+            .await;
+        let target = server.host_with_port();
+
+        let proxy = spawn_proxy(move |mut stream| async move {
+            let head = read_head_full(&mut stream).await;
+            assert!(head.starts_with(&format!("CONNECT {target} HTTP/1.1\r\n")));
+            assert!(head.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+            let upstream = TcpStream::connect(target).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+            (stream, upstream)
+        })
+        .await;
+
+        let connector = HttpProxyConnector::new(proxy)
+            .unwrap()
+            .basic_auth("user", "pass");
+        assert_eq!(get(connector, &server).await, "ok");
+    }
+
+    async fn read_head_full(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    #[tokio::test]
+    async fn socks5() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("GET", "/foo")
+            .with_body("ok")
+            .create_async()
+            .await;
+        let target: std::net::SocketAddr = server.host_with_port().parse().unwrap();
+
+        let proxy = spawn_proxy(move |mut stream| async move {
+            let mut greeting = [0; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [5, 1, 2]);
+            stream.write_all(&[5, 2]).await.unwrap();
+
+            let mut auth = [0; 11];
+            stream.read_exact(&mut auth).await.unwrap();
+            assert_eq!(&auth, b"\x01\x04user\x04pass");
+            stream.write_all(&[1, 0]).await.unwrap();
+
+            let mut request = [0; 10];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..4], [5, 1, 0, 1]);
+            assert_eq!(request[4..8], [127, 0, 0, 1]);
+            assert_eq!(u16::from_be_bytes([request[8], request[9]]), target.port());
+
+            let upstream = TcpStream::connect(target).await.unwrap();
+            stream
+                .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            (stream, upstream)
+        })
+        .await;
+
+        let connector = Socks5Connector::new(proxy).unwrap().auth("user", "pass");
+        assert_eq!(get(connector, &server).await, "ok");
+    }
+
+    #[tokio::test]
+    async fn socks5_auth_version() {
+        let (mut client, mut proxy) = tokio::io::duplex(64);
+        tokio::spawn(async move {
+            let mut buf = [0; 14];
+            proxy.read_exact(&mut buf[..3]).await.unwrap();
+            proxy.write_all(&[5, 2]).await.unwrap();
+            proxy.read_exact(&mut buf[..11]).await.unwrap();
+            // The version of the sub-negotiation is 1, not 5.
+            proxy.write_all(&[5, 0]).await.unwrap();
+        });
+        let credentials = ("user".to_owned(), "pass".to_owned());
+        let result = socks5_handshake(&mut client, "backend", 80, Some(&credentials)).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid SOCKS5 authentication reply"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::io::Error as IoError;
+
+#[cfg(feature = "nativetls")]
+pub use native::NativeTlsClientBuilder;
+
+#[cfg(feature = "__rustls")]
+pub use self::rustls::RustlsClientBuilder;
+
+#[cfg(feature = "nativetls")]
+mod native;
+#[cfg(feature = "__rustls")]
+mod rustls;
+
+/// An error while building a TLS client.
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub enum TlsError {
+    Io(IoError),
+    /// The PEM does not contain the expected item, *e.g.* no `CERTIFICATE` section.
+    NotFound(&'static str),
+    #[cfg(feature = "__rustls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+    Pem(::rustls::pki_types::pem::Error),
+    #[cfg(feature = "__rustls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+    Rustls(::rustls::Error),
+    #[cfg(feature = "__rustls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+    Verifier(::rustls::client::VerifierBuilderError),
+    #[cfg(feature = "__rustls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+    ServerName(::rustls::pki_types::InvalidDnsNameError),
+    /// No crypto provider is installed as the process default, and the features do not enable
+    /// exactly one of `rustls-ring` and `rustls-aws-lc`.
+    #[cfg(feature = "__rustls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+    NoCryptoProvider,
+    #[cfg(feature = "nativetls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "nativetls")))]
+    NativeTls(native_tls::Error),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::NotFound(item) => write!(f, "No {item} found"),
+            #[cfg(feature = "__rustls")]
+            Self::Pem(e) => write!(f, "Invalid PEM: {e}"),
+            #[cfg(feature = "__rustls")]
+            Self::Rustls(e) => write!(f, "Rustls error: {e}"),
+            #[cfg(feature = "__rustls")]
+            Self::Verifier(e) => write!(f, "Cannot build the certificate verifier: {e}"),
+            #[cfg(feature = "__rustls")]
+            Self::ServerName(e) => write!(f, "Invalid server name: {e}"),
+            #[cfg(feature = "__rustls")]
+            Self::NoCryptoProvider => write!(f, "No crypto provider to choose"),
+            #[cfg(feature = "nativetls")]
+            Self::NativeTls(e) => write!(f, "Native TLS error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+/// A self-signed certificate of `backend.internal` for the tests of the builders.
+#[cfg(test)]
+const TEST_CERT: &[u8] = b"-----BEGIN CERTIFICATE-----
+MIIBpzCCAU2gAwIBAgIUYqqESHmF3TtwyB9u2oWWXEYBqeswCgYIKoZIzj0EAwIw
+GzEZMBcGA1UEAwwQYmFja2VuZC5pbnRlcm5hbDAgFw0yNjEwMTgxOTM3MDFaGA8y
+MTI2MDkyNDE5MzcwMVowGzEZMBcGA1UEAwwQYmFja2VuZC5pbnRlcm5hbDBZMBMG
+ByqGSM49AgEGCCqGSM49AwEHA0IABBRTsTJC6qa5SbNKKpqr2XkeTx/4F+gDh/Qv
+n6Qcky/UFCetELauhngtIgVUeZylhK0OVtmYc2yVt9jBgPP4jGmjbTBrMB0GA1Ud
+DgQWBBSQhv2MgRjWCe4epFcZUe9+Y6t9UDAfBgNVHSMEGDAWgBSQhv2MgRjWCe4e
+pFcZUe9+Y6t9UDAMBgNVHRMBAf8EAjAAMBsGA1UdEQQUMBKCEGJhY2tlbmQuaW50
+ZXJuYWwwCgYIKoZIzj0EAwIDSAAwRQIhAMU6r5t+9qv6CKCJsswWN19brExO3rFo
+tRdSWGEjbYPDAiAf/6HgKmmIb8xlKMEKYoxKWKq3YPzT6NkLcTdGd/eWnQ==
+-----END CERTIFICATE-----
+";
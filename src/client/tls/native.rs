@@ -0,0 +1,163 @@
+use hyper::body::Body as HttpBody;
+use hyper_util::client::legacy::connect::Connect;
+
+use super::TlsError;
+use crate::client::{Builder, Client, HttpConnector, NativeTlsConnector};
+
+/// Builds a [`Client`] with a [`NativeTlsConnector`], configured at runtime.
+///
+/// ```no_run
+/// # fn run_test() -> Result<(), axum_proxy::client::TlsError> {
+/// use axum_proxy::client::NativeTlsClientBuilder;
+/// # use hyper::body::Incoming;
+///
+/// let client = NativeTlsClientBuilder::new()
+///     .add_root_certificates_pem(&std::fs::read("internal-ca.pem").unwrap())?
+///     .client_auth_pkcs12(&std::fs::read("client.p12").unwrap(), "password")?
+///     .build::<Incoming>()?;
+///
+/// let builder = axum_proxy::builder(client, "https", "internal.example.com").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "nativetls")))]
+#[expect(clippy::module_name_repetitions)]
+pub struct NativeTlsClientBuilder {
+    tls: native_tls::TlsConnectorBuilder,
+}
+
+impl Default for NativeTlsClientBuilder {
+    fn default() -> Self {
+        Self {
+            tls: native_tls::TlsConnector::builder(),
+        }
+    }
+}
+
+impl NativeTlsClientBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts all the `CERTIFICATE`s in `pem`, in addition to the system roots.
+    ///
+    /// # Errors
+    ///
+    /// When `pem` is invalid or does not contain any certificate.
+    pub fn add_root_certificates_pem(mut self, pem: &[u8]) -> Result<Self, TlsError> {
+        const END: &[u8] = b"-----END CERTIFICATE-----";
+
+        let mut rest = pem;
+        let mut found = false;
+        while let Some(i) = rest.windows(END.len()).position(|w| w == END) {
+            let (cert, tail) = rest.split_at(i + END.len());
+            let cert = native_tls::Certificate::from_pem(cert).map_err(TlsError::NativeTls)?;
+            self.tls.add_root_certificate(cert);
+            found = true;
+            rest = tail;
+        }
+        if !found {
+            return Err(TlsError::NotFound("certificate"));
+        }
+        Ok(self)
+    }
+
+    /// Presents the certificate `chain` and its PKCS#8 private `key` to upstreams requiring
+    /// client authentication (mutual TLS).
+    ///
+    /// # Errors
+    ///
+    /// When `chain` or `key` is invalid.
+    pub fn client_auth_pem(mut self, chain: &[u8], key: &[u8]) -> Result<Self, TlsError> {
+        let identity = native_tls::Identity::from_pkcs8(chain, key).map_err(TlsError::NativeTls)?;
+        self.tls.identity(identity);
+        Ok(self)
+    }
+
+    /// Presents the identity in the PKCS#12 archive `der` to upstreams requiring client
+    /// authentication (mutual TLS).
+    ///
+    /// # Errors
+    ///
+    /// When `der` is invalid, or `password` is wrong.
+    pub fn client_auth_pkcs12(mut self, der: &[u8], password: &str) -> Result<Self, TlsError> {
+        let identity =
+            native_tls::Identity::from_pkcs12(der, password).map_err(TlsError::NativeTls)?;
+        self.tls.identity(identity);
+        Ok(self)
+    }
+
+    /// # Errors
+    ///
+    /// When the configuration is rejected by the TLS library.
+    pub fn build<B>(self) -> Result<Client<NativeTlsConnector<HttpConnector>, B>, TlsError>
+    where
+        B: HttpBody + Send,
+        B::Data: Send,
+    {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        self.build_with_connector(http)
+    }
+
+    /// Same as [`Self::build()`], except that the TCP connections are made by `connector`, *e.g.*
+    /// an [`HttpProxyConnector`](crate::client::HttpProxyConnector).
+    ///
+    /// # Errors
+    ///
+    /// When the configuration is rejected by the TLS library.
+    pub fn build_with_connector<C, B>(
+        self,
+        connector: C,
+    ) -> Result<Client<NativeTlsConnector<C>, B>, TlsError>
+    where
+        NativeTlsConnector<C>: Connect + Clone,
+        B: HttpBody + Send,
+        B::Data: Send,
+    {
+        let tls = self.tls.build().map_err(TlsError::NativeTls)?;
+        let mut conn = NativeTlsConnector::from((connector, tls.into()));
+        conn.https_only(true);
+        Ok(Builder::new(hyper_util::rt::TokioExecutor::new()).build(conn))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::tls::TEST_CERT as CERT;
+
+    #[test]
+    fn pem() {
+        let pem = [CERT, CERT].concat();
+        let builder = NativeTlsClientBuilder::new()
+            .add_root_certificates_pem(&pem)
+            .unwrap();
+        assert!(builder.build::<String>().is_ok());
+    }
+
+    #[test]
+    fn invalid_pem() {
+        assert!(matches!(
+            NativeTlsClientBuilder::new().add_root_certificates_pem(
+                b"-----BEGIN CERTIFICATE-----\n!!!!\n-----END CERTIFICATE-----\n"
+            ),
+            Err(TlsError::NativeTls(_))
+        ));
+        assert!(matches!(
+            NativeTlsClientBuilder::new().client_auth_pkcs12(b"", "password"),
+            Err(TlsError::NativeTls(_))
+        ));
+    }
+
+    #[test]
+    fn pem_not_found() {
+        for pem in [&b""[..], b"-----BEGIN CERTIFICATE-----\n"] {
+            assert!(matches!(
+                NativeTlsClientBuilder::new().add_root_certificates_pem(pem),
+                Err(TlsError::NotFound("certificate"))
+            ));
+        }
+    }
+}
@@ -0,0 +1,506 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use hyper::body::Body as HttpBody;
+use hyper_util::client::legacy::connect::Connect;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::pem::{Error as PemError, PemObject};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+use super::TlsError;
+use crate::client::{Builder, Client, HttpConnector, RustlsConnector};
+
+/// Builds a [`Client`] with a [`RustlsConnector`], configured at runtime.
+///
+/// By default, the trusted roots are the same as [`rustls_default()`](crate::client::rustls_default()),
+/// plus ones added by [`Self::add_root_certificates_pem()`]. The scheme and HTTP versions are also
+/// the same.
+///
+/// ```no_run
+/// # fn run_test() -> Result<(), axum_proxy::client::TlsError> {
+/// use axum_proxy::client::RustlsClientBuilder;
+/// # use hyper::body::Incoming;
+///
+/// let client = RustlsClientBuilder::new()
+///     .add_root_certificates_pem(&std::fs::read("internal-ca.pem").unwrap())?
+///     .client_auth_pem(
+///         &std::fs::read("client.pem").unwrap(),
+///         &std::fs::read("client.key").unwrap(),
+///     )?
+///     .build::<Incoming>()?;
+///
+/// let builder = axum_proxy::builder(client, "https", "internal.example.com").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug, Default)]
+pub struct RustlsClientBuilder {
+    roots: Vec<CertificateDer<'static>>,
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    server_name: Option<ServerName<'static>>,
+    pins: Vec<[u8; 32]>,
+    disable_built_in_roots: bool,
+    danger_accept_invalid_certs: bool,
+}
+
+impl RustlsClientBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts all the `CERTIFICATE`s in `pem`, in addition to the default roots.
+    ///
+    /// # Errors
+    ///
+    /// When `pem` is invalid or does not contain any certificate.
+    pub fn add_root_certificates_pem(mut self, pem: &[u8]) -> Result<Self, TlsError> {
+        self.roots.extend(certificates_pem(pem)?);
+        Ok(self)
+    }
+
+    /// Trusts all the `CERTIFICATE`s in the PEM file at `path`, in addition to the default
+    /// roots.
+    ///
+    /// # Errors
+    ///
+    /// When the file cannot be read, is invalid or does not contain any certificate.
+    pub fn add_root_certificates_pem_file<P: AsRef<Path>>(self, path: P) -> Result<Self, TlsError> {
+        let pem = std::fs::read(path).map_err(TlsError::Io)?;
+        self.add_root_certificates_pem(&pem)
+    }
+
+    /// Does not trust the default roots, *i.e.* trusts only the ones added by
+    /// [`Self::add_root_certificates_pem()`] or [`Self::add_root_certificates_pem_file()`].
+    #[must_use]
+    pub fn disable_built_in_roots(mut self, disable: bool) -> Self {
+        self.disable_built_in_roots = disable;
+        self
+    }
+
+    /// Accepts *any* certificate presented by upstreams, including expired, self-signed or
+    /// issued for another name.
+    ///
+    /// # Warning
+    ///
+    /// This makes the connections open to man-in-the-middle attacks. Use this only to develop
+    /// against local upstreams. Pins by [`Self::pin_spki_sha256()`] are still checked.
+    #[must_use]
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.danger_accept_invalid_certs = accept;
+        self
+    }
+
+    /// Presents the certificate `chain` and its private `key` to upstreams requiring client
+    /// authentication (mutual TLS).
+    ///
+    /// `chain` starts with the end-entity certificate. `key` may be PKCS#1, PKCS#8 or SEC1.
+    ///
+    /// # Errors
+    ///
+    /// When `chain` or `key` is invalid or empty.
+    pub fn client_auth_pem(mut self, chain: &[u8], key: &[u8]) -> Result<Self, TlsError> {
+        let chain = certificates_pem(chain)?;
+        let key = PrivateKeyDer::from_pem_slice(key).map_err(|e| match e {
+            PemError::NoItemsFound => TlsError::NotFound("private key"),
+            e => TlsError::Pem(e),
+        })?;
+        self.client_auth = Some((chain, key));
+        Ok(self)
+    }
+
+    /// Sends `name` as the SNI, and verifies the upstream certificate against `name`, instead of
+    /// the host of the request URI.
+    ///
+    /// This is useful when the upstream is addressed by its IP address.
+    ///
+    /// # Errors
+    ///
+    /// When `name` is neither a DNS name nor an IP address.
+    pub fn server_name<S: Into<String>>(mut self, name: S) -> Result<Self, TlsError> {
+        let name = ServerName::try_from(name.into()).map_err(TlsError::ServerName)?;
+        self.server_name = Some(name);
+        Ok(self)
+    }
+
+    /// Accepts the upstream only if the SHA-256 hash of the `SubjectPublicKeyInfo` of its
+    /// certificate is `hash`.
+    ///
+    /// This is checked in addition to the usual verification. When called several times, any one
+    /// of the hashes is accepted, *e.g.* to rotate keys.
+    ///
+    /// The hash can be computed by
+    ///
+    /// ```sh
+    /// openssl x509 -in cert.pem -pubkey -noout \
+    ///     | openssl pkey -pubin -outform der \
+    ///     | openssl dgst -sha256
+    /// ```
+    #[must_use]
+    pub fn pin_spki_sha256(mut self, hash: [u8; 32]) -> Self {
+        self.pins.push(hash);
+        self
+    }
+
+    /// The crypto provider is the process default if one is installed, *e.g.* by
+    /// [`CryptoProvider::install_default()`], or else the one of the `rustls-ring` or
+    /// `rustls-aws-lc` feature if exactly one of them is enabled.
+    ///
+    /// # Errors
+    ///
+    /// When no crypto provider can be chosen, the configuration is rejected by `rustls`, or the
+    /// native roots cannot be loaded.
+    pub fn build<B>(self) -> Result<Client<RustlsConnector<HttpConnector>, B>, TlsError>
+    where
+        B: HttpBody + Send,
+        B::Data: Send,
+    {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        self.build_with_connector(http)
+    }
+
+    /// Same as [`Self::build()`], except that the TCP connections are made by `connector`, *e.g.*
+    /// an [`HttpProxyConnector`](crate::client::HttpProxyConnector).
+    ///
+    /// # Errors
+    ///
+    /// When no crypto provider can be chosen, the configuration is rejected by `rustls`, or the
+    /// native roots cannot be loaded.
+    pub fn build_with_connector<C, B>(
+        self,
+        connector: C,
+    ) -> Result<Client<RustlsConnector<C>, B>, TlsError>
+    where
+        RustlsConnector<C>: Connect + Clone,
+        B: HttpBody + Send,
+        B::Data: Send,
+    {
+        let provider = crypto_provider()?;
+        let config = rustls::ClientConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(TlsError::Rustls)?;
+
+        let verifier: Arc<dyn ServerCertVerifier> = if self.danger_accept_invalid_certs {
+            log::warn!("The certificates of upstreams are not verified");
+            Arc::new(NoVerifier(provider.signature_verification_algorithms))
+        } else {
+            let mut roots = rustls::RootCertStore::empty();
+            if !self.disable_built_in_roots {
+                add_built_in_roots(&mut roots)?;
+            }
+            for cert in self.roots {
+                roots.add(cert).map_err(TlsError::Rustls)?;
+            }
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(TlsError::Verifier)?
+        };
+        let verifier: Arc<dyn ServerCertVerifier> = if self.pins.is_empty() {
+            verifier
+        } else {
+            Arc::new(PinnedVerifier {
+                inner: verifier,
+                pins: self.pins,
+            })
+        };
+
+        let config = config
+            .dangerous()
+            .with_custom_certificate_verifier(verifier);
+        let config = match self.client_auth {
+            Some((chain, key)) => config
+                .with_client_auth_cert(chain, key)
+                .map_err(TlsError::Rustls)?,
+            None => config.with_no_client_auth(),
+        };
+
+        let conn = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(config)
+            .https_only();
+        let conn = match self.server_name {
+            Some(name) => {
+                conn.with_server_name_resolver(hyper_rustls::FixedServerNameResolver::new(name))
+            },
+            None => conn,
+        };
+        #[cfg(feature = "http1")]
+        let conn = conn.enable_http1();
+        #[cfg(feature = "rustls-http2")]
+        let conn = conn.enable_http2();
+        Ok(
+            Builder::new(hyper_util::rt::TokioExecutor::new())
+                .build(conn.wrap_connector(connector)),
+        )
+    }
+}
+
+/// The process default crypto provider, or the one of the enabled feature.
+fn crypto_provider() -> Result<Arc<CryptoProvider>, TlsError> {
+    if let Some(provider) = CryptoProvider::get_default() {
+        return Ok(Arc::clone(provider));
+    }
+    #[cfg(all(feature = "rustls-ring", not(feature = "rustls-aws-lc")))]
+    let provider = Some(rustls::crypto::ring::default_provider());
+    #[cfg(all(feature = "rustls-aws-lc", not(feature = "rustls-ring")))]
+    let provider = Some(rustls::crypto::aws_lc_rs::default_provider());
+    #[cfg(not(any(
+        all(feature = "rustls-ring", not(feature = "rustls-aws-lc")),
+        all(feature = "rustls-aws-lc", not(feature = "rustls-ring"))
+    )))]
+    let provider = None;
+    provider.map(Arc::new).ok_or(TlsError::NoCryptoProvider)
+}
+
+/// The roots of [`rustls_default()`](crate::client::rustls_default()).
+#[cfg_attr(
+    any(feature = "rustls-webpki-roots", not(feature = "rustls-native-roots")),
+    expect(clippy::unnecessary_wraps)
+)]
+fn add_built_in_roots(roots: &mut rustls::RootCertStore) -> Result<(), TlsError> {
+    #[cfg(feature = "rustls-webpki-roots")]
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    #[cfg(all(not(feature = "rustls-webpki-roots"), feature = "rustls-native-roots"))]
+    {
+        let native = rustls_native_certs::load_native_certs();
+        if let Some(e) = native.errors.into_iter().next() {
+            if native.certs.is_empty() {
+                return Err(TlsError::Io(std::io::Error::other(e)));
+            }
+        }
+        roots.add_parsable_certificates(native.certs);
+    }
+    Ok(())
+}
+
+/// Accepts any certificate, but still checks the handshake signatures made with it.
+#[derive(Debug)]
+struct NoVerifier(WebPkiSupportedAlgorithms);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_schemes()
+    }
+}
+
+/// Verifies certificates by `inner`, and then checks their SPKI against `pins`.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        let hash = spki_sha256(end_entity)
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::BadEncoding))?;
+        if self.pins.contains(&hash) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// The SHA-256 hash of the `subjectPublicKeyInfo` of the end-entity certificate `cert`.
+fn spki_sha256(cert: &CertificateDer<'_>) -> Result<[u8; 32], webpki::Error> {
+    let cert = webpki::EndEntityCert::try_from(cert)?;
+    Ok(Sha256::digest(cert.subject_public_key_info()).into())
+}
+
+fn certificates_pem(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(TlsError::Pem)?;
+    if certs.is_empty() {
+        return Err(TlsError::NotFound("certificate"));
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::tls::TEST_CERT as CERT;
+
+    const CERT_SPKI_SHA256: [u8; 32] = [
+        0x9e, 0x44, 0x3e, 0xab, 0xc5, 0x3a, 0x37, 0xae, 0x92, 0x0f, 0x19, 0xe4, 0x10, 0xfc, 0x89,
+        0xa4, 0x65, 0xdf, 0x3c, 0x23, 0xc7, 0x64, 0xc1, 0xa8, 0x8e, 0x4c, 0xb7, 0xb3, 0xc2, 0x69,
+        0xc1, 0xe4,
+    ];
+
+    #[test]
+    fn spki_hash() {
+        let cert = CertificateDer::from_pem_slice(CERT).unwrap();
+        assert_eq!(spki_sha256(&cert).unwrap(), CERT_SPKI_SHA256);
+
+        assert!(spki_sha256(&CertificateDer::from(&cert[..100])).is_err());
+    }
+
+    #[cfg(feature = "rustls-ring")]
+    #[test]
+    fn pinned() {
+        let cert = CertificateDer::from_pem_slice(CERT).unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert.clone()).unwrap();
+        let inner: Arc<dyn ServerCertVerifier> = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(rustls::crypto::ring::default_provider()),
+        )
+        .build()
+        .unwrap();
+
+        let name = ServerName::try_from("backend.internal").unwrap();
+        let verify = |pins| {
+            let verifier = PinnedVerifier {
+                inner: inner.clone(),
+                pins,
+            };
+            verifier.verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+        };
+
+        assert!(verify(vec![CERT_SPKI_SHA256]).is_ok());
+        assert!(matches!(
+            verify(vec![[0; 32]]),
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure
+            ))
+        ));
+    }
+
+    #[cfg(feature = "rustls-ring")]
+    #[test]
+    fn accept_invalid_certs() {
+        let cert = CertificateDer::from_pem_slice(CERT).unwrap();
+        let name = ServerName::try_from("other.internal").unwrap();
+        let inner: Arc<dyn ServerCertVerifier> = Arc::new(NoVerifier(
+            rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        ));
+        assert!(inner
+            .verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+            .is_ok());
+
+        let pinned = PinnedVerifier {
+            inner,
+            pins: vec![[0; 32]],
+        };
+        assert!(pinned
+            .verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+            .is_err());
+    }
+
+    #[test]
+    fn pem_file() {
+        let path = std::env::temp_dir().join(format!("axum-proxy-{}.pem", std::process::id()));
+        std::fs::write(&path, CERT).unwrap();
+        let builder = RustlsClientBuilder::new()
+            .disable_built_in_roots(true)
+            .add_root_certificates_pem_file(&path)
+            .unwrap();
+        assert_eq!(builder.roots.len(), 1);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            RustlsClientBuilder::new().add_root_certificates_pem_file(&path),
+            Err(TlsError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn pem_not_found() {
+        assert!(matches!(
+            RustlsClientBuilder::new().add_root_certificates_pem(b""),
+            Err(TlsError::NotFound("certificate"))
+        ));
+        assert!(matches!(
+            RustlsClientBuilder::new().add_root_certificates_pem(
+                b"-----BEGIN CERTIFICATE-----\n!!!!\n-----END CERTIFICATE-----\n"
+            ),
+            Err(TlsError::Pem(_))
+        ));
+    }
+
+    #[cfg(all(feature = "rustls-ring", feature = "rustls-aws-lc"))]
+    #[test]
+    fn no_crypto_provider() {
+        // Both providers are available, and none is installed as the default.
+        assert!(matches!(
+            RustlsClientBuilder::new().build::<String>(),
+            Err(TlsError::NoCryptoProvider)
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+use std::future::Future;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::Uri;
+use hyper_util::client::legacy::connect::{Connected, Connection};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tower_service::Service;
+
+/// A connector to a Unix domain socket.
+///
+/// Every connection goes to the same socket, whatever the [`Uri`] of the request is.
+///
+/// ```
+/// # use axum_proxy::client::UnixConnector;
+/// let a = UnixConnector::new("/run/app.sock");
+/// let b = UnixConnector::new("unix:///run/app.sock");
+/// assert_eq!(a.path(), b.path());
+/// ```
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug, Clone)]
+pub struct UnixConnector {
+    path: Arc<Path>,
+}
+
+impl UnixConnector {
+    /// `path` may be prefixed with `unix://`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let path = path
+            .to_str()
+            .and_then(|s| s.strip_prefix("unix://"))
+            .map_or(path, Path::new);
+        Self {
+            path: PathBuf::from(path).into(),
+        }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Service<Uri> for UnixConnector {
+    type Response = TokioIo<UnixStream>;
+    type Error = IoError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _dst: Uri) -> Self::Future {
+        let path = Arc::clone(&self.path);
+        Box::pin(async move {
+            let stream = tokio::net::UnixStream::connect(&path).await?;
+            Ok(TokioIo::new(UnixStream(stream)))
+        })
+    }
+}
+
+/// A stream returned by [`UnixConnector`].
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct UnixStream(tokio::net::UnixStream);
+
+impl Connection for UnixStream {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<(), IoError>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixListener;
+
+    use crate::{OneshotService, ReplaceAll};
+
+    #[tokio::test]
+    async fn request() {
+        let path = std::env::temp_dir().join(format!("axum-proxy-{}.sock", std::process::id()));
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        }
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let mut len = 0;
+            while !buf[..len].ends_with(b"\r\n\r\n") {
+                len += stream.read(&mut buf[len..]).await.unwrap();
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+            String::from_utf8(buf[..len].to_vec()).unwrap()
+        });
+
+        let mut svc = OneshotService::unix(
+            format!("unix://{}", path.display()),
+            ReplaceAll("foo", "goo"),
+        );
+        let req = Request::builder()
+            .uri("https://test.com/foo")
+            .body(String::new())
+            .unwrap();
+        let response = tower_service::Service::call(&mut svc, req)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.into_body().collect().await.unwrap().to_bytes(),
+            "ok"
+        );
+
+        let head = server.await.unwrap();
+        assert!(head.starts_with("GET /goo HTTP/1.1\r\n"));
+        assert!(head.contains("host: localhost\r\n"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
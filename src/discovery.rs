@@ -0,0 +1,642 @@
+//! Discovers upstream servers from a file, and reloads it while running.
+//!
+//! A [`FileDiscovery`] reads a JSON or TOML file listing the upstreams:
+//!
+//! ```json
+//! { "upstreams": ["http://10.0.0.1:8080", "http://backend.internal"] }
+//! ```
+//!
+//! ```toml
+//! upstreams = ["http://10.0.0.1:8080", "http://backend.internal"]
+//! ```
+//!
+//! and keeps an [`Upstreams`] up to date. A [`DiscoveredService`] sends each request to the next
+//! upstream of the set, in round robin.
+//!
+//! All the upstreams of a file must have the same scheme, and the client of the service must be
+//! able to connect to it: [`client::http_default()`](crate::client::http_default) only speaks
+//! `http`, so list the upstreams as `https` only with a TLS client.
+//!
+//! ```no_run
+//! # async fn run_test() {
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use axum_proxy::discovery::{DiscoveredService, FileDiscovery};
+//! use axum_proxy::{client, Identity};
+//! # use hyper::body::Incoming;
+//!
+//! let discovery = FileDiscovery::new("/etc/proxy/upstreams.toml").interval(Duration::from_secs(2));
+//! discovery.load().await.unwrap();
+//!
+//! let svc = DiscoveredService::new(
+//!     Arc::new(client::http_default::<Incoming>()),
+//!     discovery.upstreams(),
+//!     Identity,
+//! );
+//!
+//! // Polls the file in the background
+//! let _handle = discovery.spawn();
+//! # }
+//! ```
+
+use std::convert::Infallible;
+use std::fmt;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use http::uri::{Authority, Scheme, Uri};
+use http::{Request, Response, Version};
+use hyper::body::{Body as HttpBody, Incoming};
+use hyper_util::client::legacy::connect::Connect;
+use hyper_util::client::legacy::Client;
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+use tower_service::Service;
+
+use crate::future::{Options, RevProxyFuture};
+use crate::rewrite::{HeaderRewriter, RequestRewriter};
+use crate::Error;
+
+type BoxErr = Box<dyn std::error::Error + Send + Sync>;
+
+/// An upstream server, *i.e.* where the requests are sent to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    pub scheme: Scheme,
+    pub authority: Authority,
+}
+
+impl TryFrom<&str> for Upstream {
+    type Error = InvalidUpstream;
+
+    /// Parses `scheme://authority`, optionally followed by `/`.
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let uri = Uri::try_from(value).map_err(|_| InvalidUpstream::Uri)?;
+        if !matches!(
+            uri.path_and_query().map(http::uri::PathAndQuery::as_str),
+            None | Some("/")
+        ) {
+            return Err(InvalidUpstream::HasPath);
+        }
+
+        let http::uri::Parts {
+            scheme, authority, ..
+        } = uri.into_parts();
+        match (scheme, authority) {
+            (Some(scheme), Some(authority)) => Ok(Self { scheme, authority }),
+            (None, _) => Err(InvalidUpstream::NoScheme),
+            (_, None) => Err(InvalidUpstream::NoAuthority),
+        }
+    }
+}
+
+/// The reason why an entry is not a valid [`Upstream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidUpstream {
+    Uri,
+    NoScheme,
+    NoAuthority,
+    HasPath,
+}
+
+impl fmt::Display for InvalidUpstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uri => write!(f, "not a valid uri"),
+            Self::NoScheme => write!(f, "missing scheme"),
+            Self::NoAuthority => write!(f, "missing authority"),
+            Self::HasPath => write!(f, "must not have a path or a query"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidUpstream {}
+
+/// The live set of [`Upstream`]s.
+///
+/// Clones share the same set, so an update through one of them is seen by all the others.
+#[derive(Debug, Clone, Default)]
+pub struct Upstreams {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    list: RwLock<Arc<[Upstream]>>,
+    next: AtomicUsize,
+}
+
+impl Upstreams {
+    #[must_use]
+    pub fn new(list: Vec<Upstream>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                list: RwLock::new(list.into()),
+                next: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// A snapshot of the current set.
+    ///
+    /// # Panics
+    ///
+    /// When the lock is poisoned.
+    #[must_use]
+    pub fn get(&self) -> Arc<[Upstream]> {
+        Arc::clone(&self.inner.list.read().expect("upstreams lock poisoned"))
+    }
+
+    /// Replaces the current set.
+    ///
+    /// # Panics
+    ///
+    /// When the lock is poisoned.
+    pub fn set(&self, list: Vec<Upstream>) {
+        *self.inner.list.write().expect("upstreams lock poisoned") = list.into();
+    }
+
+    /// The next upstream in round robin, or `None` if the set is empty.
+    #[must_use]
+    pub fn next(&self) -> Option<Upstream> {
+        let list = self.get();
+        if list.is_empty() {
+            return None;
+        }
+        let i = self.inner.next.fetch_add(1, Ordering::Relaxed);
+        Some(list[i % list.len()].clone())
+    }
+}
+
+/// The format of the file read by [`FileDiscovery`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// `.json` or `.toml`.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Parses the content of a file.
+    ///
+    /// # Errors
+    ///
+    /// When the content is not valid in this format, an entry is not a valid [`Upstream`], or the
+    /// entries do not all have the same scheme.
+    pub fn parse(self, content: &str) -> Result<Vec<Upstream>, DiscoveryError> {
+        #[derive(Deserialize)]
+        struct File {
+            upstreams: Vec<String>,
+        }
+
+        let file: File = match self {
+            Self::Json => serde_json::from_str(content).map_err(DiscoveryError::Json)?,
+            Self::Toml => toml::from_str(content).map_err(DiscoveryError::Toml)?,
+        };
+
+        let list = file
+            .upstreams
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                Upstream::try_from(entry.as_str())
+                    .map_err(|reason| DiscoveryError::InvalidUpstream { index, reason })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(first) = list.first() {
+            if let Some(index) = list.iter().position(|u| u.scheme != first.scheme) {
+                return Err(DiscoveryError::MixedSchemes { index });
+            }
+        }
+
+        Ok(list)
+    }
+}
+
+/// An error while (re)loading a file.
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub enum DiscoveryError {
+    Io(IoError),
+    /// The format cannot be inferred from the file extension.
+    UnknownFormat,
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    /// The `index`-th entry of `upstreams` is invalid.
+    InvalidUpstream {
+        index: usize,
+        reason: InvalidUpstream,
+    },
+    /// The `index`-th entry of `upstreams` has not the same scheme as the first one.
+    MixedSchemes {
+        index: usize,
+    },
+}
+
+impl fmt::Display for DiscoveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Cannot read the file: {e}"),
+            Self::UnknownFormat => write!(f, "Unknown file format"),
+            Self::Json(e) => write!(f, "Invalid JSON: {e}"),
+            Self::Toml(e) => write!(f, "Invalid TOML: {e}"),
+            Self::InvalidUpstream { index, reason } => {
+                write!(f, "Invalid upstream at index {index}: {reason}")
+            },
+            Self::MixedSchemes { index } => {
+                write!(
+                    f,
+                    "Upstream at index {index} has not the same scheme as the first one"
+                )
+            },
+        }
+    }
+}
+
+impl std::error::Error for DiscoveryError {}
+
+/// Reads upstreams from a file, and reloads them when the file changes.
+///
+/// The file is polled every [`interval`](Self::interval) (5 seconds by default). When the new
+/// content cannot be read or parsed, the error is logged out at [error](`log::error`) level and
+/// the current set is kept as is, until the modification time or size of the file changes again.
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct FileDiscovery {
+    path: PathBuf,
+    format: Option<Format>,
+    interval: Duration,
+    upstreams: Upstreams,
+}
+
+impl FileDiscovery {
+    /// The format is inferred from the extension of `path`. See [`Format::from_path()`].
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        Self {
+            format: Format::from_path(&path),
+            path,
+            interval: Duration::from_secs(5),
+            upstreams: Upstreams::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Updates `upstreams` instead of a new set.
+    #[must_use]
+    pub fn with_upstreams(mut self, upstreams: Upstreams) -> Self {
+        self.upstreams = upstreams;
+        self
+    }
+
+    /// The set updated by this discovery.
+    #[must_use]
+    pub fn upstreams(&self) -> Upstreams {
+        self.upstreams.clone()
+    }
+
+    /// Reads the file now, and replaces the set on success.
+    ///
+    /// # Errors
+    ///
+    /// When the file cannot be read or parsed. The current set is not modified then.
+    pub async fn load(&self) -> Result<(), DiscoveryError> {
+        let format = self.format.ok_or(DiscoveryError::UnknownFormat)?;
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(DiscoveryError::Io)?;
+        let list = format.parse(&content)?;
+
+        log::info!(
+            "Loaded {} upstream(s) from {}",
+            list.len(),
+            self.path.display()
+        );
+        self.upstreams.set(list);
+
+        Ok(())
+    }
+
+    /// Polls the file in a new task, reloading it whenever its modification time or size
+    /// changes, or only its size where the modification time is not supported.
+    ///
+    /// # Panics
+    ///
+    /// When called outside of a tokio runtime.
+    #[must_use]
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            // The stamp of the last content loaded or rejected, so that an invalid file is
+            // reported once rather than on every tick
+            let mut last: Option<Result<(Option<SystemTime>, u64), std::io::ErrorKind>> = None;
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                let stamp = match tokio::fs::metadata(&self.path).await {
+                    Ok(meta) => Some(Ok((meta.modified().ok(), meta.len()))),
+                    Err(e) => {
+                        let kind = Some(Err(e.kind()));
+                        if last != kind {
+                            log::error!("{}: {}", self.path.display(), DiscoveryError::Io(e));
+                        }
+                        last = kind;
+                        continue;
+                    },
+                };
+                if stamp == last {
+                    continue;
+                }
+
+                if let Err(e) = self.load().await {
+                    log::error!("{}: {e}", self.path.display());
+                }
+                last = stamp;
+            }
+        })
+    }
+}
+
+/// A [`Service<Request<B>>`] that sends a request to one of [`Upstreams`], sharing a [`Client`].
+///
+/// When the set is empty, the service returns [`Error::NoUpstream`].
+#[derive(Debug)]
+pub struct DiscoveredService<Pr, C, B = Incoming> {
+    client: Arc<Client<C, B>>,
+    upstreams: Upstreams,
+    path: Pr,
+    options: Options,
+}
+
+impl<Pr: Clone, C, B> Clone for DiscoveredService<Pr, C, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            upstreams: self.upstreams.clone(),
+            path: self.path.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<Pr, C, B> DiscoveredService<Pr, C, B> {
+    pub fn new(client: Arc<Client<C, B>>, upstreams: Upstreams, path: Pr) -> Self {
+        Self {
+            client,
+            upstreams,
+            path,
+            options: Options::default(),
+        }
+    }
+
+    /// Sets the HTTP version of the requests to the upstreams.
+    ///
+    /// See [`ReusedService::upstream_version()`](crate::ReusedService::upstream_version).
+    #[must_use]
+    pub fn upstream_version(mut self, version: Version) -> Self {
+        self.options.version = Some(version);
+        self
+    }
+
+    /// Limits the size of the request bodies.
+    ///
+    /// See [`ReusedService::max_body_size()`](crate::ReusedService::max_body_size).
+    #[must_use]
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.options.max_body_size = Some(limit);
+        self
+    }
+
+    /// Rewrites the headers, and the method, of the requests.
+    ///
+    /// See [`ReusedService::request_headers()`](crate::ReusedService::request_headers).
+    #[must_use]
+    pub fn request_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.request_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Rewrites the headers of the responses.
+    ///
+    /// See [`ReusedService::response_headers()`](crate::ReusedService::response_headers).
+    #[must_use]
+    pub fn response_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.response_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Switches to gRPC mode.
+    ///
+    /// See [`ReusedService::grpc()`](crate::ReusedService::grpc).
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    #[must_use]
+    pub fn grpc(mut self) -> Self {
+        self.options.grpc();
+        self
+    }
+}
+
+impl<C, B, Pr> Service<Request<B>> for DiscoveredService<Pr, C, B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static + Unpin,
+    B::Data: Send,
+    B::Error: Into<BoxErr>,
+    Pr: RequestRewriter,
+{
+    type Response = Result<Response<Incoming>, Error>;
+    type Error = Infallible;
+    type Future = RevProxyFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        match self.upstreams.next() {
+            Some(upstream) => RevProxyFuture::new(
+                &self.client,
+                req,
+                &upstream.scheme,
+                &upstream.authority,
+                &mut self.path,
+                &self.options,
+            ),
+            None => RevProxyFuture::error(Error::NoUpstream, &self.options),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::uri::Parts;
+    use mockito::ServerGuard;
+
+    use super::*;
+    use crate::{client, test_helper, ReplaceAll};
+
+    fn upstream(s: &str) -> Upstream {
+        Upstream::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let json = r#"{ "upstreams": ["https://a.com:8080", "https://b.com/"] }"#;
+        assert_eq!(
+            Format::Json.parse(json).unwrap(),
+            vec![upstream("https://a.com:8080"), upstream("https://b.com")]
+        );
+
+        let toml = r#"upstreams = ["http://a.com:8080"]"#;
+        assert_eq!(
+            Format::Toml.parse(toml).unwrap(),
+            vec![upstream("http://a.com:8080")]
+        );
+
+        let json = r#"{ "upstreams": ["http://a.com", "b.com", "http://c.com/x"] }"#;
+        assert!(matches!(
+            Format::Json.parse(json),
+            Err(DiscoveryError::InvalidUpstream {
+                index: 1,
+                reason: InvalidUpstream::NoScheme
+            })
+        ));
+
+        let json = r#"{ "upstreams": ["http://a.com", "http://b.com", "https://c.com"] }"#;
+        assert!(matches!(
+            Format::Json.parse(json),
+            Err(DiscoveryError::MixedSchemes { index: 2 })
+        ));
+
+        assert!(matches!(
+            Format::Toml.parse("upstreams = "),
+            Err(DiscoveryError::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn round_robin() {
+        let upstreams = Upstreams::default();
+        assert_eq!(upstreams.next(), None);
+
+        upstreams.set(vec![upstream("http://a.com"), upstream("http://b.com")]);
+        assert_eq!(upstreams.next(), Some(upstream("http://a.com")));
+        assert_eq!(upstreams.next(), Some(upstream("http://b.com")));
+        assert_eq!(upstreams.next(), Some(upstream("http://a.com")));
+    }
+
+    #[tokio::test]
+    async fn reload_keeps_current_on_error() {
+        let path = std::env::temp_dir().join(format!("axum-proxy-{}.json", std::process::id()));
+        let discovery = FileDiscovery::new(&path);
+
+        std::fs::write(&path, r#"{ "upstreams": ["http://a.com"] }"#).unwrap();
+        assert!(discovery.load().await.is_ok());
+        assert_eq!(&*discovery.upstreams().get(), [upstream("http://a.com")]);
+
+        std::fs::write(&path, r#"{ "upstreams": ["http://b.com", "/"] }"#).unwrap();
+        assert!(discovery.load().await.is_err());
+        assert_eq!(&*discovery.upstreams().get(), [upstream("http://a.com")]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spawn() {
+        let path = std::env::temp_dir().join(format!("axum-proxy-{}.toml", std::process::id()));
+        std::fs::write(&path, r#"upstreams = ["http://a.com"]"#).unwrap();
+        let discovery = FileDiscovery::new(&path).interval(Duration::from_secs(1));
+        let upstreams = discovery.upstreams();
+        let handle = discovery.spawn();
+
+        let wait_for = |expected: Upstream| {
+            let upstreams = upstreams.clone();
+            async move {
+                for _ in 0..10 {
+                    if *upstreams.get() == [expected.clone()] {
+                        return;
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                panic!("{expected:?} has not been loaded");
+            }
+        };
+        wait_for(upstream("http://a.com")).await;
+
+        std::fs::write(&path, r#"upstreams = ["http://b.com:8080"]"#).unwrap();
+        wait_for(upstream("http://b.com:8080")).await;
+
+        handle.abort();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    async fn make_svc() -> (
+        ServerGuard,
+        DiscoveredService<ReplaceAll<&'static str>, client::HttpConnector, String>,
+    ) {
+        let server = mockito::Server::new_async().await;
+        let Parts {
+            scheme, authority, ..
+        } = Uri::try_from(&server.url()).unwrap().into_parts();
+
+        let upstreams = Upstreams::new(vec![Upstream {
+            scheme: scheme.unwrap(),
+            authority: authority.unwrap(),
+        }]);
+        let svc = DiscoveredService::new(
+            Arc::new(client::http_default()),
+            upstreams,
+            ReplaceAll("foo", "goo"),
+        );
+        (server, svc)
+    }
+
+    #[tokio::test]
+    async fn match_path() {
+        let (mut server, mut svc) = make_svc().await;
+        test_helper::match_path(&mut server, &mut svc).await;
+    }
+
+    #[tokio::test]
+    async fn no_upstream() {
+        let (_server, mut svc) = make_svc().await;
+        svc.upstreams.set(vec![]);
+
+        let req = Request::builder()
+            .uri("https://test.com/foo")
+            .body(String::new())
+            .unwrap();
+        let result = svc.call(req).await.unwrap();
+        assert!(matches!(result, Err(Error::NoUpstream)));
+    }
+}
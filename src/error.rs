@@ -1,17 +1,48 @@
 use std::error::Error as StdError;
 use std::fmt;
+use std::io::Error as IoError;
 
 #[cfg(feature = "axum")]
 use axum::response::{IntoResponse, Response};
+#[cfg(feature = "axum")]
+use http::header::{HeaderName, CONTENT_TYPE};
+use http::uri::Uri;
 use http::Error as HttpError;
 #[cfg(feature = "axum")]
 use http::StatusCode;
 use hyper_util::client::legacy::Error as HyperError;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     InvalidUri(HttpError),
     RequestFailed(HyperError),
+    /// There was no upstream server to send the request to.
+    NoUpstream,
+    /// The target of the request is not allowed.
+    Forbidden(Uri),
+    /// The address of the client is not in the extensions of the request, as the app is not
+    /// served with connect info.
+    NoClientAddr,
+    /// Failed to connect to the target of a tunnel.
+    Connect(IoError),
+    /// The upstream did not send the response headers within the `grpc-timeout` of the request, or
+    /// was idle for longer than the timeout of [`Streaming`](crate::streaming::Streaming).
+    Timeout,
+    /// The body of the request is larger than the limit of the service.
+    PayloadTooLarge,
+    /// The path of the request is rejected by [`Normalize`](crate::rewrite::Normalize) or
+    /// [`Confine`](crate::rewrite::Confine).
+    InvalidPath(String),
+    /// An error of a service in gRPC mode.
+    ///
+    /// With the `axum` feature, this is rendered as a trailers-only gRPC response, with the status
+    /// `DEADLINE_EXCEEDED` for [`Error::Timeout`], `RESOURCE_EXHAUSTED` for
+    /// [`Error::PayloadTooLarge`], `INVALID_ARGUMENT` for [`Error::InvalidPath`],
+    /// `PERMISSION_DENIED` for [`Error::Forbidden`], `INTERNAL` for [`Error::InvalidUri`] and
+    /// [`Error::NoClientAddr`], and `UNAVAILABLE` for [`Error::NoUpstream`], [`Error::Connect`]
+    /// and [`Error::RequestFailed`].
+    Grpc(Box<Error>),
 }
 
 impl fmt::Display for Error {
@@ -23,6 +54,30 @@ impl fmt::Display for Error {
             Self::RequestFailed(e) => {
                 write!(f, "Request failed: {e}")
             },
+            Self::NoUpstream => {
+                write!(f, "No upstream available")
+            },
+            Self::Forbidden(uri) => {
+                write!(f, "Forbidden target: {uri}")
+            },
+            Self::NoClientAddr => {
+                write!(f, "No client address, serve the app with connect info")
+            },
+            Self::Connect(e) => {
+                write!(f, "Connection failed: {e}")
+            },
+            Self::Timeout => {
+                write!(f, "Upstream timed out")
+            },
+            Self::PayloadTooLarge => {
+                write!(f, "Request body too large")
+            },
+            Self::InvalidPath(path) => {
+                write!(f, "Invalid path: {path}")
+            },
+            Self::Grpc(e) => {
+                write!(f, "gRPC: {e}")
+            },
         }
     }
 }
@@ -34,6 +89,92 @@ impl StdError for Error {}
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         log::error!("{self}");
-        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        match self {
+            Self::NoUpstream => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+            Self::Forbidden(_) => StatusCode::FORBIDDEN.into_response(),
+            Self::Connect(_) => StatusCode::BAD_GATEWAY.into_response(),
+            Self::Timeout => StatusCode::GATEWAY_TIMEOUT.into_response(),
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+            Self::InvalidPath(_) => StatusCode::BAD_REQUEST.into_response(),
+            Self::InvalidUri(_) | Self::RequestFailed(_) | Self::NoClientAddr => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            },
+            Self::Grpc(e) => {
+                let message = grpc_message(&e.to_string());
+                (
+                    [
+                        (CONTENT_TYPE, "application/grpc"),
+                        (HeaderName::from_static("grpc-status"), e.grpc_status()),
+                        (HeaderName::from_static("grpc-message"), &message),
+                    ],
+                    (),
+                )
+                    .into_response()
+            },
+        }
+    }
+}
+
+impl Error {
+    /// The code of `grpc-status` for this error, which is wrapped in [`Error::Grpc`].
+    #[cfg(any(feature = "axum", feature = "grpc-web"))]
+    pub(crate) fn grpc_status(&self) -> &'static str {
+        match self {
+            Self::Timeout => "4",
+            Self::PayloadTooLarge => "8",
+            Self::InvalidPath(_) => "3",
+            Self::Forbidden(_) => "7",
+            Self::InvalidUri(_) | Self::NoClientAddr => "13",
+            Self::NoUpstream | Self::Connect(_) | Self::RequestFailed(_) => "14",
+            Self::Grpc(e) => e.grpc_status(),
+        }
+    }
+}
+
+/// Percent-encodes `message` for the `grpc-message` header.
+#[cfg(any(feature = "axum", feature = "grpc-web"))]
+pub(crate) fn grpc_message(message: &str) -> String {
+    use std::fmt::Write;
+
+    let mut encoded = String::with_capacity(message.len());
+    for b in message.bytes() {
+        if (b' '..=b'~').contains(&b) && b != b'%' {
+            encoded.push(char::from(b));
+        } else {
+            write!(encoded, "%{b:02X}").unwrap();
+        }
+    }
+    encoded
+}
+
+#[cfg(all(test, feature = "axum"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn grpc_response() {
+        let response = Error::Grpc(Box::new(Error::Timeout)).into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/grpc");
+        assert_eq!(response.headers()["grpc-status"], "4");
+        assert_eq!(response.headers()["grpc-message"], "Upstream timed out");
+
+        let response = Error::Grpc(Box::new(Error::NoUpstream)).into_response();
+        assert_eq!(response.headers()["grpc-status"], "14");
+
+        let response =
+            Error::Grpc(Box::new(Error::Forbidden(Uri::from_static("/")))).into_response();
+        assert_eq!(response.headers()["grpc-status"], "7");
+
+        let response = Error::Grpc(Box::new(Error::PayloadTooLarge)).into_response();
+        assert_eq!(response.headers()["grpc-status"], "8");
+
+        let error = Error::InvalidPath("/../admin".to_owned());
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn encode_grpc_message() {
+        assert_eq!(grpc_message("a b%c\n\u{e9}"), "a b%25c%0A%C3%A9");
     }
 }
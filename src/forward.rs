@@ -0,0 +1,255 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::uri::Scheme;
+use http::{Request, Response, Version};
+use hyper::body::{Body as HttpBody, Incoming};
+use hyper_util::client::legacy::connect::Connect;
+use hyper_util::client::legacy::Client;
+use tower_service::Service;
+
+use crate::client::HttpConnector;
+use crate::future::{Options, RevProxyFuture};
+use crate::policy::HostPolicy;
+use crate::rewrite::{HeaderRewriter, Identity};
+use crate::Error;
+
+type BoxErr = Box<dyn std::error::Error + Send + Sync>;
+
+/// A [`Service<Request<B>>`] that sends a request to the absolute-form target of the request
+/// itself, sharing a [`Client`]. That is, a forward proxy.
+///
+/// The scheme and the authority of the target must be allowed by the [`HostPolicy`], with the port
+/// `80` for `http` and `443` for `https` if omitted; otherwise, or if the target is not
+/// absolute, the service returns [`Error::Forbidden`]. The `Proxy-Authorization` and
+/// `Proxy-Connection` headers are removed. To proxy `https` targets, the [`Client`] must support
+/// them, e.g. [`client::https_default()`](crate::client::https_default).
+///
+/// The policy checks the target as written, so a name resolving to a denied address is only
+/// denied if the client resolves names with a [`Resolver`](crate::policy::Resolver).
+///
+/// ```
+/// # async fn run_test() {
+/// # use std::sync::Arc;
+/// # use axum_proxy::policy::HostPolicy;
+/// # use axum_proxy::{client, ForwardService};
+/// # use tower_service::Service;
+/// # use http_body_util::Empty;
+/// # use http::Request;
+/// # use hyper::body::Bytes;
+/// let policy = HostPolicy::new().allow("*.example.com", Some(80));
+/// let mut svc = ForwardService::new(Arc::new(client::http_default()), policy);
+///
+/// let req = Request::builder()
+///     .uri("http://www.example.com/foo")
+///     .body(Empty::<Bytes>::new())
+///     .unwrap();
+/// let _res = svc.call(req).await.unwrap();
+/// # }
+/// ```
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct ForwardService<C = HttpConnector, B = Incoming> {
+    client: Arc<Client<C, B>>,
+    policy: Arc<HostPolicy>,
+    options: Options,
+}
+
+impl<C, B> Clone for ForwardService<C, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            policy: self.policy.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<C, B> ForwardService<C, B> {
+    pub fn new(client: Arc<Client<C, B>>, policy: HostPolicy) -> Self {
+        Self {
+            client,
+            policy: Arc::new(policy),
+            options: Options::default(),
+        }
+    }
+
+    /// Sets the HTTP version of the requests to the upstreams.
+    ///
+    /// See [`ReusedService::upstream_version()`](crate::ReusedService::upstream_version).
+    #[must_use]
+    pub fn upstream_version(mut self, version: Version) -> Self {
+        self.options.version = Some(version);
+        self
+    }
+
+    /// Limits the size of the request bodies.
+    ///
+    /// See [`ReusedService::max_body_size()`](crate::ReusedService::max_body_size).
+    #[must_use]
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.options.max_body_size = Some(limit);
+        self
+    }
+
+    /// Rewrites the headers, and the method, of the requests.
+    ///
+    /// See [`ReusedService::request_headers()`](crate::ReusedService::request_headers).
+    #[must_use]
+    pub fn request_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.request_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Rewrites the headers of the responses.
+    ///
+    /// See [`ReusedService::response_headers()`](crate::ReusedService::response_headers).
+    #[must_use]
+    pub fn response_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.response_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Switches to gRPC mode.
+    ///
+    /// See [`ReusedService::grpc()`](crate::ReusedService::grpc).
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    #[must_use]
+    pub fn grpc(mut self) -> Self {
+        self.options.grpc();
+        self
+    }
+}
+
+impl<C, B> Service<Request<B>> for ForwardService<C, B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static + Unpin,
+    B::Data: Send,
+    B::Error: Into<BoxErr>,
+{
+    type Response = Result<Response<Incoming>, Error>;
+    type Error = Infallible;
+    type Future = RevProxyFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let uri = req.uri();
+        let default_port = match uri.scheme() {
+            Some(scheme) if *scheme == Scheme::HTTP => Some(80),
+            Some(scheme) if *scheme == Scheme::HTTPS => Some(443),
+            _ => None,
+        };
+        let target = uri
+            .scheme()
+            .zip(uri.authority())
+            .zip(default_port)
+            .filter(|((_, authority), port)| self.policy.is_allowed_authority(authority, *port));
+        let Some(((scheme, authority), _)) = target else {
+            return RevProxyFuture::error(Error::Forbidden(uri.clone()), &self.options);
+        };
+
+        let scheme = scheme.clone();
+        let authority = authority.clone();
+        let headers = req.headers_mut();
+        headers.remove("proxy-authorization");
+        headers.remove("proxy-connection");
+        RevProxyFuture::new(
+            &self.client,
+            req,
+            &scheme,
+            &authority,
+            &mut Identity,
+            &self.options,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::uri::Uri;
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::client;
+
+    fn make_svc(policy: HostPolicy) -> ForwardService<HttpConnector, String> {
+        ForwardService::new(Arc::new(client::http_default()), policy)
+    }
+
+    #[tokio::test]
+    async fn forward() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("GET", "/foo?bar=baz")
+            .match_header("proxy-authorization", mockito::Matcher::Missing)
+            .match_header("x-forwarded", "1")
+            .with_body("ok")
+            .create_async()
+            .await;
+
+        let uri = Uri::try_from(server.url()).unwrap();
+        let port = uri.port_u16().unwrap();
+        let mut svc = make_svc(HostPolicy::new().allow("127.0.0.1", Some(port))).request_headers(
+            HeaderRewriter::new().set(
+                http::HeaderName::from_static("x-forwarded"),
+                http::HeaderValue::from_static("1"),
+            ),
+        );
+        let request = Request::get(format!("{}/foo?bar=baz", server.url()))
+            .header("proxy-authorization", "Basic Zm9vOmJhcg==")
+            .body(String::new())
+            .unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "ok");
+    }
+
+    #[tokio::test]
+    async fn resolver() {
+        use crate::policy::Resolver;
+
+        let server = mockito::Server::new_async().await;
+        let port = Uri::try_from(server.url()).unwrap().port_u16().unwrap();
+        let policy = HostPolicy::new()
+            .allow("*", None)
+            .deny("127.0.0.1", None)
+            .deny("::1", None);
+        let connector = HttpConnector::new_with_resolver(Resolver::new(policy.clone()));
+        let mut svc = ForwardService::new(
+            Arc::new(client::with_connector_default::<_, String>(connector)),
+            policy,
+        );
+        let request = Request::get(format!("http://localhost:{port}/"))
+            .body(String::new())
+            .unwrap();
+        let result = svc.call(request).await.unwrap();
+        assert!(matches!(result, Err(Error::Forbidden(uri)) if uri.host() == Some("localhost")));
+    }
+
+    #[tokio::test]
+    async fn forbidden() {
+        let mut svc = make_svc(
+            HostPolicy::new()
+                .allow("*.example.com", None)
+                .deny("internal.example.com", None),
+        );
+        for uri in [
+            "/foo",
+            "http://example.com/foo",
+            "http://internal.example.com/foo",
+            "http://internal.example.com./foo",
+            "ftp://www.example.com/foo",
+        ] {
+            let request = Request::get(uri).body(String::new()).unwrap();
+            let result = svc.call(request).await.unwrap();
+            assert!(matches!(result, Err(Error::Forbidden(_))), "{uri}");
+        }
+    }
+}
@@ -1,22 +1,85 @@
 use std::convert::Infallible;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
+use http::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, TE};
 use http::uri::{Authority, Scheme};
-use http::{Error as HttpError, Request, Response};
+use http::{Request, Response, Uri, Version};
+use http_body::{Frame, SizeHint};
 use hyper::body::{Body as HttpBody, Incoming};
 use hyper_util::client::legacy::connect::Connect;
-use hyper_util::client::legacy::{Client, ResponseFuture};
+use hyper_util::client::legacy::{Client, Error as HyperError, ResponseFuture};
+use tokio::time::Sleep;
 
-use crate::rewrite::PathRewriter;
+use crate::rewrite::{HeaderRewriter, RequestRewriter};
 use crate::Error;
 
 type BoxErr = Box<dyn std::error::Error + Send + Sync>;
 
+/// Settings of a service, applied to every request in [`RevProxyFuture::new()`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Options {
+    /// Overrides the HTTP version of the requests to upstreams.
+    pub(crate) version: Option<Version>,
+    /// Proxies gRPC: sends `te: trailers`, honours `grpc-timeout` until the response headers and
+    /// returns [`Error::Grpc`].
+    pub(crate) grpc: bool,
+    /// The maximum size of the request bodies.
+    pub(crate) max_body_size: Option<u64>,
+    /// Rewrites the headers of the requests, after their path.
+    pub(crate) request_headers: Option<Arc<HeaderRewriter>>,
+    /// Rewrites the headers of the responses.
+    pub(crate) response_headers: Option<Arc<HeaderRewriter>>,
+}
+
+impl Options {
+    #[cfg(feature = "http2")]
+    pub(crate) fn grpc(&mut self) {
+        self.version = Some(Version::HTTP_2);
+        self.grpc = true;
+    }
+}
+
+/// Converts an error of the [`Client`] for the request to `uri`, which is
+/// [`Error::PayloadTooLarge`] if a [`LimitedBody`] has exceeded its limit, and
+/// [`Error::Forbidden`] if the connector has denied the address, as
+/// [`policy::Resolver`](crate::policy::Resolver) does.
+fn request_failed(error: HyperError, uri: Uri) -> Error {
+    let mut source = std::error::Error::source(&error);
+    while let Some(e) = source {
+        if matches!(e.downcast_ref(), Some(Error::PayloadTooLarge)) {
+            return Error::PayloadTooLarge;
+        }
+        if e.downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+        {
+            return Error::Forbidden(uri);
+        }
+        source = e.source();
+    }
+    Error::RequestFailed(error)
+}
+
+/// Wraps `error` into [`Error::Grpc`] if `grpc` is set.
+fn wrap(grpc: bool, error: Error) -> Error {
+    if grpc {
+        Error::Grpc(Box::new(error))
+    } else {
+        error
+    }
+}
+
 #[expect(clippy::module_name_repetitions)]
 pub struct RevProxyFuture {
-    inner: Result<ResponseFuture, Option<HttpError>>,
+    inner: Result<ResponseFuture, Option<Error>>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    grpc: bool,
+    response_headers: Option<Arc<HeaderRewriter>>,
+    /// The URI sent upstream, for [`Error::Forbidden`].
+    uri: Uri,
 }
 
 impl RevProxyFuture {
@@ -26,19 +89,89 @@ impl RevProxyFuture {
         scheme: &Scheme,
         authority: &Authority,
         path: &mut Pr,
+        options: &Options,
     ) -> Self
     where
         C: Connect + Clone + Send + Sync + 'static,
         B: HttpBody + Send + 'static + Unpin,
         B::Data: Send,
         B::Error: Into<BoxErr>,
-        Pr: PathRewriter,
+        Pr: RequestRewriter,
     {
+        if let Some(limit) = options.max_body_size {
+            let length = req
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok());
+            if length
+                .unwrap_or_default()
+                .max(req.body().size_hint().lower())
+                > limit
+            {
+                return Self::error(Error::PayloadTooLarge, options);
+            }
+        }
+
+        if let Some(version) = options.version {
+            *req.version_mut() = version;
+        }
+
+        let mut deadline = None;
+        if options.grpc {
+            req.headers_mut()
+                .insert(TE, HeaderValue::from_static("trailers"));
+            deadline = grpc_timeout(req.headers()).map(|t| Box::pin(tokio::time::sleep(t)));
+        }
+
+        let mut uri = Uri::default();
         let inner = path
-            .rewrite_uri(&mut req, scheme, authority)
-            .map(|()| client.request(req))
-            .map_err(Some);
-        Self { inner }
+            .rewrite_request(&mut req, scheme, authority)
+            .map(|()| {
+                if let Some(headers) = &options.request_headers {
+                    headers.rewrite_request(&mut req);
+                }
+                uri.clone_from(req.uri());
+                client.request(req)
+            })
+            .map_err(|e| Some(wrap(options.grpc, e)));
+        Self {
+            inner,
+            deadline,
+            grpc: options.grpc,
+            response_headers: options.response_headers.clone(),
+            uri,
+        }
+    }
+
+    /// A future which resolves to `error` immediately.
+    pub(crate) fn error(error: Error, options: &Options) -> Self {
+        Self {
+            inner: Err(Some(wrap(options.grpc, error))),
+            deadline: None,
+            grpc: options.grpc,
+            response_headers: None,
+            uri: Uri::default(),
+        }
+    }
+}
+
+/// Parses the `grpc-timeout` header, such as `100m` for 100 milliseconds.
+fn grpc_timeout(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get("grpc-timeout")?.to_str().ok()?;
+    let (amount, unit) = value.split_at_checked(value.len().checked_sub(1)?)?;
+    if amount.is_empty() || amount.len() > 8 || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
     }
 }
 
@@ -46,15 +179,138 @@ impl Future for RevProxyFuture {
     type Output = Result<Result<Response<Incoming>, Error>, Infallible>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match &mut self.inner {
+        let this = &mut *self;
+        match &mut this.inner {
             Ok(fut) => match Future::poll(Pin::new(fut), cx) {
-                Poll::Ready(res) => Poll::Ready(Ok(res.map_err(Error::RequestFailed))),
-                Poll::Pending => Poll::Pending,
+                Poll::Ready(res) => {
+                    let res = res.map(|mut res| {
+                        if let Some(headers) = &this.response_headers {
+                            headers.rewrite_response(&mut res);
+                        }
+                        res
+                    });
+                    let uri = std::mem::take(&mut this.uri);
+                    Poll::Ready(Ok(res.map_err(|e| wrap(this.grpc, request_failed(e, uri)))))
+                },
+                Poll::Pending => {
+                    let deadline = this.deadline.as_mut();
+                    if deadline.is_some_and(|d| d.as_mut().poll(cx).is_ready()) {
+                        this.inner = Err(None);
+                        return Poll::Ready(Ok(Err(wrap(this.grpc, Error::Timeout))));
+                    }
+                    Poll::Pending
+                },
             },
             Err(e) => match e.take() {
-                Some(e) => Poll::Ready(Ok(Err(Error::InvalidUri(e)))),
+                Some(e) => Poll::Ready(Ok(Err(e))),
                 None => unreachable!("RevProxyFuture::poll() is called after ready"),
             },
         }
     }
 }
+
+/// A request body failing with [`Error::PayloadTooLarge`] once more than a limit has been read.
+///
+/// The services check the `Content-Length` of the requests against their
+/// [`max_body_size()`](crate::ReusedService::max_body_size), but the bodies without one, such as
+/// chunked ones, must be limited while they are streamed. For that, use this as the body type of
+/// the [`Client`] and wrap the requests, e.g. with `tower::ServiceExt::map_request()`. The
+/// services return [`Error::PayloadTooLarge`] when the limit is exceeded.
+///
+/// ```
+/// # #[cfg(feature = "axum")] {
+/// use axum::body::Body;
+/// use axum::extract::Request;
+/// use axum::Router;
+/// use axum_proxy::{Identity, LimitedBody};
+/// use tower::ServiceExt;
+///
+/// const LIMIT: u64 = 1024 * 1024;
+///
+/// let svc = axum_proxy::builder_http::<LimitedBody<Body>, _>("example.com")
+///     .unwrap()
+///     .max_body_size(LIMIT)
+///     .build(Identity)
+///     .map_request(|req: Request| req.map(|body| LimitedBody::new(body, LIMIT)));
+/// let app: Router = Router::new().route_service("/upload", svc);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LimitedBody<B> {
+    inner: B,
+    remaining: u64,
+}
+
+impl<B> LimitedBody<B> {
+    pub fn new(inner: B, limit: u64) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B> HttpBody for LimitedBody<B>
+where
+    B: HttpBody + Unpin,
+    B::Error: Into<BoxErr>,
+{
+    type Data = B::Data;
+    type Error = BoxErr;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = match std::task::ready!(Pin::new(&mut self.inner).poll_frame(cx)) {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+            None => return Poll::Ready(None),
+        };
+        if let Some(data) = frame.data_ref() {
+            let len = u64::try_from(hyper::body::Buf::remaining(data)).unwrap_or(u64::MAX);
+            match self.remaining.checked_sub(len) {
+                Some(remaining) => self.remaining = remaining,
+                None => return Poll::Ready(Some(Err(Error::PayloadTooLarge.into()))),
+            }
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_grpc_timeout() {
+        let parse = |value| {
+            let mut headers = HeaderMap::new();
+            headers.insert("grpc-timeout", HeaderValue::from_static(value));
+            grpc_timeout(&headers)
+        };
+        assert_eq!(parse("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse("2M"), Some(Duration::from_secs(120)));
+        assert_eq!(parse("3S"), Some(Duration::from_secs(3)));
+        assert_eq!(parse("100m"), Some(Duration::from_millis(100)));
+        assert_eq!(parse("5u"), Some(Duration::from_micros(5)));
+        assert_eq!(parse("7n"), Some(Duration::from_nanos(7)));
+        assert_eq!(parse("m"), None);
+        assert_eq!(parse("123456789S"), None);
+        assert_eq!(parse("-1S"), None);
+        assert_eq!(parse("10x"), None);
+        assert_eq!(grpc_timeout(&HeaderMap::new()), None);
+    }
+}
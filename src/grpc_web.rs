@@ -0,0 +1,543 @@
+//! Translates gRPC-Web requests from browsers into gRPC toward the upstreams.
+//!
+//! [`GrpcWeb`] wraps a service in gRPC mode, such as one built by
+//! [`ReusedServiceBuilder::grpc()`](crate::ReusedServiceBuilder::grpc). For the requests with the
+//! content type `application/grpc-web` or `application/grpc-web-text`:
+//!
+//! - the content type is replaced with `application/grpc`, and the body of `-text` requests is
+//!   decoded from base64,
+//! - the trailers of a gRPC response are encoded into the last frame of the body, which is
+//!   encoded into base64 for `-text` requests, and
+//! - [`Error::Grpc`] is returned as a trailers-only gRPC-Web response.
+//!
+//! The other requests, and the responses whose content type is not `application/grpc`, are passed
+//! as is.
+//!
+//! ```no_run
+//! # #[cfg(feature = "axum")] {
+//! use axum::body::Body;
+//! use axum::Router;
+//! use axum_proxy::grpc_web::{GrpcWeb, RequestBody};
+//! use axum_proxy::Identity;
+//!
+//! # async fn run() {
+//! let grpc = axum_proxy::builder_h2c::<RequestBody<Body>, _>("backend:50051")
+//!     .unwrap()
+//!     .grpc();
+//! let app = Router::new().route_service("/{*rpc}", GrpcWeb::new(grpc.build(Identity)));
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+//! axum::serve(listener, app).await.unwrap();
+//! # }
+//! # }
+//! ```
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response};
+use http_body::{Frame, SizeHint};
+use hyper::body::{Body as HttpBody, Buf, Bytes, Incoming};
+use tower_service::Service;
+
+use crate::Error;
+
+type BoxErr = Box<dyn std::error::Error + Send + Sync>;
+
+/// Encoding of the body of gRPC-Web messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// `application/grpc-web`
+    Binary,
+    /// `application/grpc-web-text`, encoded into base64
+    Text,
+}
+
+impl Encoding {
+    /// Reads the content type of a request, returning the suffix such as `+proto`.
+    fn from_content_type(content_type: &str) -> Option<(Self, &str)> {
+        if let Some(suffix) = content_type.strip_prefix("application/grpc-web-text") {
+            Some((Self::Text, suffix))
+        } else {
+            content_type
+                .strip_prefix("application/grpc-web")
+                .map(|suffix| (Self::Binary, suffix))
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Binary => "application/grpc-web",
+            Self::Text => "application/grpc-web-text",
+        }
+    }
+}
+
+/// A [`Service<Request<B>>`] translating gRPC-Web into gRPC for the inner service.
+///
+/// See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct GrpcWeb<S> {
+    inner: S,
+}
+
+impl<S> GrpcWeb<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> Service<Request<B>> for GrpcWeb<S>
+where
+    S: Service<
+        Request<RequestBody<B>>,
+        Response = Result<Response<Incoming>, Error>,
+        Error = Infallible,
+    >,
+    S::Future: Unpin,
+{
+    type Response = Result<Response<ResponseBody>, Error>;
+    type Error = Infallible;
+    type Future = GrpcWebFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let (mut parts, body) = req.into_parts();
+
+        let found = parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Encoding::from_content_type)
+            .map(|(encoding, suffix)| (encoding, format!("application/grpc{suffix}")));
+        let encoding = found.map(|(encoding, content_type)| {
+            if let Ok(value) = HeaderValue::try_from(content_type) {
+                parts.headers.insert(CONTENT_TYPE, value);
+            }
+            parts.headers.remove(CONTENT_LENGTH);
+            encoding
+        });
+
+        let body = RequestBody {
+            inner: body,
+            text: encoding == Some(Encoding::Text),
+            buf: Vec::new(),
+        };
+        GrpcWebFuture {
+            inner: self.inner.call(Request::from_parts(parts, body)),
+            encoding,
+        }
+    }
+}
+
+/// The future of [`GrpcWeb`].
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct GrpcWebFuture<F> {
+    inner: F,
+    encoding: Option<Encoding>,
+}
+
+impl<F> Future for GrpcWebFuture<F>
+where
+    F: Future<Output = Result<Result<Response<Incoming>, Error>, Infallible>> + Unpin,
+{
+    type Output = Result<Result<Response<ResponseBody>, Error>, Infallible>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Ok(result) = ready!(Pin::new(&mut self.inner).poll(cx));
+        let Some(encoding) = self.encoding else {
+            return Poll::Ready(Ok(result.map(|res| {
+                res.map(|inner| ResponseBody {
+                    inner: Some(inner),
+                    encoding: None,
+                })
+            })));
+        };
+
+        let response = match result {
+            Ok(res) => {
+                let (mut parts, inner) = res.into_parts();
+                let suffix = parts
+                    .headers
+                    .get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("application/grpc"));
+                // Not a gRPC response, such as an error page of a load balancer
+                let Some(suffix) = suffix else {
+                    let body = ResponseBody {
+                        inner: Some(inner),
+                        encoding: None,
+                    };
+                    return Poll::Ready(Ok(Ok(Response::from_parts(parts, body))));
+                };
+                let content_type = format!("{}{suffix}", encoding.content_type());
+                if let Ok(value) = HeaderValue::try_from(content_type) {
+                    parts.headers.insert(CONTENT_TYPE, value);
+                }
+                parts.headers.remove(CONTENT_LENGTH);
+
+                let body = ResponseBody {
+                    inner: Some(inner),
+                    encoding: Some(encoding),
+                };
+                Response::from_parts(parts, body)
+            },
+            Err(Error::Grpc(e)) => {
+                let mut res = Response::new(ResponseBody {
+                    inner: None,
+                    encoding: None,
+                });
+                let headers = res.headers_mut();
+                headers.insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static(encoding.content_type()),
+                );
+                headers.insert("grpc-status", HeaderValue::from_static(e.grpc_status()));
+                if let Ok(message) =
+                    HeaderValue::try_from(crate::error::grpc_message(&e.to_string()))
+                {
+                    headers.insert("grpc-message", message);
+                }
+                log::error!("{e}");
+                res
+            },
+            Err(e) => return Poll::Ready(Ok(Err(e))),
+        };
+        Poll::Ready(Ok(Ok(response)))
+    }
+}
+
+/// The body of the requests to the inner service of [`GrpcWeb`].
+///
+/// Decodes the body of `application/grpc-web-text` requests from base64.
+#[derive(Debug)]
+pub struct RequestBody<B> {
+    inner: B,
+    text: bool,
+    /// Base64 characters not decoded yet.
+    buf: Vec<u8>,
+}
+
+impl<B> RequestBody<B> {
+    /// Decodes the complete base64 quanta in `self.buf`.
+    ///
+    /// Each message may be encoded separately, so padding may appear in the middle.
+    fn decode(&mut self) -> Result<Bytes, BoxErr> {
+        self.buf.retain(|b| !b.is_ascii_whitespace());
+        let end = self.buf.len() / 4 * 4;
+        let mut decoded = Vec::with_capacity(end / 4 * 3);
+        let mut start = 0;
+        for quantum_end in (4..=end).step_by(4) {
+            if self.buf[quantum_end - 1] == b'=' || quantum_end == end {
+                STANDARD.decode_vec(&self.buf[start..quantum_end], &mut decoded)?;
+                start = quantum_end;
+            }
+        }
+        self.buf.drain(..end);
+        Ok(decoded.into())
+    }
+}
+
+impl<B> HttpBody for RequestBody<B>
+where
+    B: HttpBody + Unpin,
+    B::Error: Into<BoxErr>,
+{
+    type Data = Bytes;
+    type Error = BoxErr;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        loop {
+            let frame = match ready!(Pin::new(&mut self.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                None if self.buf.is_empty() => return Poll::Ready(None),
+                None => return Poll::Ready(Some(Err("truncated base64 body".into()))),
+            };
+            let mut data = match frame.into_data() {
+                Ok(data) => data,
+                Err(frame) => {
+                    let trailers = frame.into_trailers().unwrap_or_default();
+                    return Poll::Ready(Some(Ok(Frame::trailers(trailers))));
+                },
+            };
+            if !self.text {
+                let data = data.copy_to_bytes(data.remaining());
+                return Poll::Ready(Some(Ok(Frame::data(data))));
+            }
+
+            while data.has_remaining() {
+                let chunk = data.chunk();
+                let len = chunk.len();
+                self.buf.extend_from_slice(chunk);
+                data.advance(len);
+            }
+            let decoded = self.decode()?;
+            if !decoded.is_empty() {
+                return Poll::Ready(Some(Ok(Frame::data(decoded))));
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.buf.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        if self.text {
+            SizeHint::default()
+        } else {
+            self.inner.size_hint()
+        }
+    }
+}
+
+/// The body of the responses from [`GrpcWeb`].
+///
+/// For gRPC-Web requests, the trailers are encoded into the last frame of the body.
+#[derive(Debug)]
+pub struct ResponseBody {
+    inner: Option<Incoming>,
+    encoding: Option<Encoding>,
+}
+
+impl ResponseBody {
+    fn encode(&self, data: Bytes) -> Bytes {
+        match self.encoding {
+            Some(Encoding::Text) => STANDARD.encode(data).into(),
+            _ => data,
+        }
+    }
+}
+
+/// Encodes `trailers` into a gRPC-Web trailer frame.
+fn trailer_frame(trailers: &HeaderMap) -> Bytes {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.extend_from_slice(b": ");
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+
+    let mut frame = Vec::with_capacity(block.len() + 5);
+    frame.push(0x80);
+    frame.extend_from_slice(&u32::try_from(block.len()).unwrap_or(u32::MAX).to_be_bytes());
+    frame.extend_from_slice(&block);
+    frame.into()
+}
+
+impl HttpBody for ResponseBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let Some(inner) = &mut self.inner else {
+            return Poll::Ready(None);
+        };
+        let frame = match ready!(Pin::new(inner).poll_frame(cx)) {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+            None => {
+                self.inner = None;
+                return Poll::Ready(None);
+            },
+        };
+        if self.encoding.is_none() {
+            return Poll::Ready(Some(Ok(frame)));
+        }
+
+        let data = match frame.into_data() {
+            Ok(data) => data,
+            Err(frame) => {
+                let trailers = frame.into_trailers().unwrap_or_default();
+                self.inner = None;
+                trailer_frame(&trailers)
+            },
+        };
+        Poll::Ready(Some(Ok(Frame::data(self.encode(data)))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.as_ref().is_none_or(HttpBody::is_end_stream)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match (&self.inner, self.encoding) {
+            (None, _) => SizeHint::with_exact(0),
+            (Some(inner), None) => inner.size_hint(),
+            (Some(_), Some(_)) => SizeHint::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http_body_util::{BodyExt, Full};
+    use hyper::server::conn::http2;
+    use hyper::service::service_fn;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{builder_h2c, Identity};
+
+    /// Spawns an h2c server answering a gRPC message `y` with the `grpc-status` trailers.
+    async fn spawn_grpc_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|_: Request<Incoming>| async {
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                let body = Full::new(Bytes::from_static(b"\0\0\0\0\x01y"))
+                    .with_trailers(async { Some(Ok::<_, Infallible>(trailers)) });
+                Response::builder()
+                    .header("content-type", "application/grpc")
+                    .body(body)
+            });
+            http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn translate() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("POST", "/pkg.Svc/Call")
+            .match_header("content-type", "application/grpc+proto")
+            .match_header("te", "trailers")
+            .match_body(b"\0\0\0\0\x01x".to_vec())
+            .with_header("content-type", "application/grpc+proto")
+            .with_body(b"\0\0\0\0\x01y")
+            .create_async()
+            .await;
+
+        let grpc = builder_h2c(server.host_with_port()).unwrap().grpc();
+        let mut svc = GrpcWeb::new(grpc.build(Identity));
+        let request = Request::post("http://test.com/pkg.Svc/Call")
+            .header("content-type", "application/grpc-web-text+proto")
+            .body(Full::new(Bytes::from_static(b"AAAAAAF4")))
+            .unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/grpc-web-text+proto"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "AAAAAAF5");
+    }
+
+    #[tokio::test]
+    async fn trailers() {
+        let grpc = builder_h2c(spawn_grpc_server().await).unwrap().grpc();
+        let mut svc = GrpcWeb::new(grpc.build(Identity));
+        let request = Request::post("http://test.com/pkg.Svc/Call")
+            .header("content-type", "application/grpc-web")
+            .body(Full::new(Bytes::from_static(b"\0\0\0\0\x01x")))
+            .unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        assert_eq!(response.headers()["content-type"], "application/grpc-web");
+        let body = response.into_body().collect().await.unwrap();
+        assert!(body.trailers().is_none());
+        assert_eq!(
+            body.to_bytes(),
+            &b"\0\0\0\0\x01y\x80\0\0\0\x10grpc-status: 0\r\n"[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn not_grpc_response() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("POST", "/pkg.Svc/Call")
+            .with_status(502)
+            .with_header("content-type", "text/html")
+            .with_body("Bad Gateway")
+            .create_async()
+            .await;
+
+        let grpc = builder_h2c(server.host_with_port()).unwrap().grpc();
+        let mut svc = GrpcWeb::new(grpc.build(Identity));
+        let request = Request::post("http://test.com/pkg.Svc/Call")
+            .header("content-type", "application/grpc-web-text")
+            .body(Full::new(Bytes::from_static(b"AAAAAAF4")))
+            .unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        assert_eq!(response.status(), 502);
+        assert_eq!(response.headers()["content-type"], "text/html");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "Bad Gateway");
+    }
+
+    #[tokio::test]
+    async fn decode_text() {
+        let mut body = RequestBody {
+            inner: Full::new(Bytes::from_static(b"AAAAAAE=\nAAAAAAJ4eQ==")),
+            text: true,
+            buf: Vec::new(),
+        };
+        let mut decoded = Vec::new();
+        while let Some(frame) = body.frame().await {
+            decoded.extend_from_slice(&frame.unwrap().into_data().unwrap());
+        }
+        assert_eq!(decoded, b"\0\0\0\0\x01\0\0\0\0\x02xy");
+    }
+
+    #[tokio::test]
+    async fn truncated_text() {
+        let body = RequestBody {
+            inner: Full::new(Bytes::from_static(b"AAAAA")),
+            text: true,
+            buf: Vec::new(),
+        };
+        assert!(body.collect().await.is_err());
+    }
+
+    #[test]
+    fn encode_trailers() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+        assert_eq!(
+            trailer_frame(&trailers),
+            &b"\x80\0\0\0\x10grpc-status: 0\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn content_type() {
+        assert_eq!(
+            Encoding::from_content_type("application/grpc-web-text+proto"),
+            Some((Encoding::Text, "+proto"))
+        );
+        assert_eq!(
+            Encoding::from_content_type("application/grpc-web"),
+            Some((Encoding::Binary, ""))
+        );
+        assert_eq!(Encoding::from_content_type("application/grpc"), None);
+    }
+}
@@ -89,6 +89,12 @@
 //! ```
 //!
 //!
+//! ## Server-Sent Events and long polls
+//!
+//! The bodies of the responses are passed on frame by frame, without buffering. To time out
+//! idle streams rather than long ones, wrap the services with [`streaming::Streaming`].
+//!
+//!
 //! # Return Types
 //!
 //! The return type ([`Future::Output`](std::future::Future::Output)) of [`ReusedService`] and
@@ -97,8 +103,12 @@
 //!
 //! The [`Error`] type implements [`IntoResponse`](axum::response::IntoResponse) if you enable the
 //! `axum`feature.
-//! It returns an empty body, with the status code `INTERNAL_SERVER_ERROR`. The description of this
-//! error will be logged out at [error](`log::error`) level in the
+//! It returns an empty body, with the status code `SERVICE_UNAVAILABLE` for [`Error::NoUpstream`],
+//! `FORBIDDEN` for [`Error::Forbidden`], `BAD_GATEWAY` for [`Error::Connect`], `GATEWAY_TIMEOUT`
+//! for [`Error::Timeout`], `PAYLOAD_TOO_LARGE` for [`Error::PayloadTooLarge`], `BAD_REQUEST` for
+//! [`Error::InvalidPath`] and `INTERNAL_SERVER_ERROR` otherwise. [`Error::Grpc`] is the exception:
+//! it is rendered as a trailers-only gRPC response instead. The description of this error will be
+//! logged out at [error](`log::error`) level in the
 //! [`into_response()`](axum::response::IntoResponse::into_response()) method.
 //!
 //!
@@ -107,7 +117,8 @@
 //! By default only `http1` is enabled.
 //!
 //! - `http1`: uses `hyper/http1`
-//! - `http2`: uses `hyper/http2`
+//! - `http2`: uses `hyper/http2`, and enables h2c upstreams with [`client::h2c_default()`] and
+//!   the gRPC mode of the services, e.g. [`ReusedService::grpc()`]
 //! - `https`: alias to `nativetls`
 //! - `nativetls`: uses the `hyper-tls` crate
 //! - `rustls`: alias to `rustls-webpki-roots`
@@ -115,6 +126,14 @@
 //! - `rustls-native-roots`: uses the `hyper-rustls` crate, with the feature `rustls-native-certs`
 //! - `rustls-http2`: `http2` plus `rustls`, and `rustls/http2` is enabled
 //! - `axum`: implements [`IntoResponse`](axum::response::IntoResponse) for [`Error`]
+//! - `proxy-chain`: connects to upstreams through an HTTP or SOCKS5 proxy, see
+//!   [`client::HttpProxyConnector`] and [`client::Socks5Connector`]
+//! - `proxy-protocol`: sends the PROXY protocol header to upstreams and accepts it from load
+//!   balancers, see [`proxy_protocol`]
+//! - `tunnel`: handles `CONNECT` requests, see [`tunnel`]
+//! - `grpc-web`: translates gRPC-Web into gRPC, see [`grpc_web`]
+//! - `discovery`: reads upstreams from a JSON or TOML file, see [`discovery`]
+//! - `serde`: reads path rewriting rules from a configuration, see [`RewriteRules`]
 //!
 //! You must turn on either `http1`or `http2`. You cannot use the services if, for example, only
 //! the `https` feature is on.
@@ -133,7 +152,30 @@ pub mod rewrite;
 pub use rewrite::*;
 
 mod future;
-pub use future::RevProxyFuture;
+pub use future::{LimitedBody, RevProxyFuture};
+
+#[cfg(feature = "discovery")]
+#[cfg_attr(docsrs, doc(cfg(feature = "discovery")))]
+pub mod discovery;
+#[cfg(feature = "grpc-web")]
+#[cfg_attr(docsrs, doc(cfg(feature = "grpc-web")))]
+pub mod grpc_web;
+pub mod policy;
+#[cfg(feature = "proxy-protocol")]
+#[cfg_attr(docsrs, doc(cfg(feature = "proxy-protocol")))]
+pub mod proxy_protocol;
+#[cfg(any(feature = "http1", feature = "http2"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "http1", feature = "http2"))))]
+pub mod streaming;
+#[cfg(feature = "tunnel")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tunnel")))]
+pub mod tunnel;
+
+#[cfg(any(feature = "http1", feature = "http2"))]
+mod forward;
+#[cfg(any(feature = "http1", feature = "http2"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "http1", feature = "http2"))))]
+pub use forward::ForwardService;
 
 #[cfg(any(feature = "http1", feature = "http2"))]
 mod oneshot;
@@ -143,6 +185,9 @@ pub use oneshot::OneshotService;
 
 #[cfg(any(feature = "http1", feature = "http2"))]
 mod reused;
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+pub use reused::builder_h2c;
 #[cfg(all(
     any(feature = "http1", feature = "http2"),
     any(feature = "https", feature = "nativetls")
@@ -167,6 +212,9 @@ pub use reused::builder_nativetls;
     doc(cfg(all(any(feature = "http1", feature = "http2"), feature = "rustls")))
 )]
 pub use reused::builder_rustls;
+#[cfg(all(any(feature = "http1", feature = "http2"), unix))]
+#[cfg_attr(docsrs, doc(cfg(all(any(feature = "http1", feature = "http2"), unix))))]
+pub use reused::builder_unix;
 #[cfg(any(feature = "http1", feature = "http2"))]
 #[cfg_attr(docsrs, doc(cfg(any(feature = "http1", feature = "http2"))))]
 pub use reused::Builder as ReusedServiceBuilder;
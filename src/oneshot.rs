@@ -1,11 +1,14 @@
 use std::convert::Infallible;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use client::HttpConnector;
 #[cfg(feature = "__rustls")]
 use client::RustlsConnector;
+#[cfg(unix)]
+use client::UnixConnector;
 use http::uri::{Authority, Scheme};
-use http::{Error as HttpError, Request, Response};
+use http::{Error as HttpError, Request, Response, Version};
 //use hyper::body::{Body, HttpBody};
 use hyper::body::{Body as HttpBody, Incoming};
 #[cfg(feature = "nativetls")]
@@ -14,8 +17,8 @@ use hyper_util::client::legacy::connect::Connect;
 use hyper_util::client::legacy::Client;
 use tower_service::Service;
 
-use crate::future::RevProxyFuture;
-use crate::rewrite::PathRewriter;
+use crate::future::{Options, RevProxyFuture};
+use crate::rewrite::{HeaderRewriter, RequestRewriter};
 use crate::{client, Error};
 
 type BoxErr = Box<dyn std::error::Error + Send + Sync>;
@@ -45,6 +48,7 @@ pub struct OneshotService<Pr, C = HttpConnector, B = Incoming> {
     scheme: Scheme,
     authority: Authority,
     path: Pr,
+    options: Options,
 }
 
 impl<Pr: Clone, C: Clone, B> Clone for OneshotService<Pr, C, B> {
@@ -55,6 +59,7 @@ impl<Pr: Clone, C: Clone, B> Clone for OneshotService<Pr, C, B> {
             scheme: self.scheme.clone(),
             authority: self.authority.clone(),
             path: self.path.clone(),
+            options: self.options.clone(),
         }
     }
 }
@@ -67,7 +72,7 @@ impl<Pr, C, B> OneshotService<Pr, C, B> {
     /// For the meaning of "scheme" and "authority", refer to the documentation of
     /// [`Uri`](http::uri::Uri).
     ///
-    /// The `path` should implement [`PathRewriter`].
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
     ///
     /// # Errors
     ///
@@ -91,8 +96,66 @@ impl<Pr, C, B> OneshotService<Pr, C, B> {
             scheme,
             authority,
             path,
+            options: Options::default(),
         })
     }
+
+    /// Sets the HTTP version of the requests to the upstream.
+    ///
+    /// By default, the version of the incoming request is kept as is, so that, for example, an
+    /// HTTP/2 request cannot be sent to an upstream only speaking HTTP/1.1. The version must be
+    /// supported by the [`Client`].
+    #[must_use]
+    pub fn upstream_version(mut self, version: Version) -> Self {
+        self.options.version = Some(version);
+        self
+    }
+
+    /// Limits the size of the request bodies to `limit` bytes.
+    ///
+    /// A request with a larger `Content-Length` or size hint is rejected with
+    /// [`Error::PayloadTooLarge`](crate::Error::PayloadTooLarge) before it is sent. The bodies
+    /// without a known length, such as chunked ones, must be wrapped with
+    /// [`LimitedBody`](crate::LimitedBody) to be limited while they are streamed.
+    #[must_use]
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.options.max_body_size = Some(limit);
+        self
+    }
+
+    /// Rewrites the headers, and the method, of the requests.
+    ///
+    /// See [`ReusedService::request_headers()`](crate::ReusedService::request_headers).
+    #[must_use]
+    pub fn request_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.request_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Rewrites the headers of the responses.
+    ///
+    /// See [`ReusedService::response_headers()`](crate::ReusedService::response_headers).
+    #[must_use]
+    pub fn response_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.response_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Switches to gRPC mode.
+    ///
+    /// The requests are sent over HTTP/2, so the [`Client`] must speak it, e.g.
+    /// [`client::h2c_default()`](crate::client::h2c_default) for plain-text upstreams. The header
+    /// `te: trailers` is set, and the trailers of the responses are passed through as is. If the
+    /// request has a `grpc-timeout` header, the upstream must send the response headers within it;
+    /// the body is not bounded by this deadline. Errors are returned as
+    /// [`Error::Grpc`](crate::Error::Grpc), rendered as trailers-only gRPC responses.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    #[must_use]
+    pub fn grpc(mut self) -> Self {
+        self.options.grpc();
+        self
+    }
 }
 
 impl<Pr, B> OneshotService<Pr, HttpConnector, B>
@@ -104,7 +167,7 @@ where
     ///
     /// For the meaning of "authority", refer to the documentation of [`Uri`](http::uri::Uri).
     ///
-    /// The `path` should implement [`PathRewriter`].
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
     ///
     /// # Errors
     ///
@@ -120,6 +183,33 @@ where
             scheme: Scheme::HTTP,
             authority,
             path,
+            options: Options::default(),
+        })
+    }
+
+    /// Use [`client::h2c_default()`] to build a client.
+    ///
+    /// For the meaning of "authority", refer to the documentation of [`Uri`](http::uri::Uri).
+    ///
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
+    ///
+    /// # Errors
+    ///
+    /// When `authority` cannot be converted into an [`Authority`].
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    pub fn h2c_default<A>(authority: A, path: Pr) -> Result<Self, HttpError>
+    where
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<HttpError>,
+    {
+        let authority = authority.try_into().map_err(Into::into)?;
+        Ok(Self {
+            client: client::h2c_default(),
+            scheme: Scheme::HTTP,
+            authority,
+            path,
+            options: Options::default(),
         })
     }
 }
@@ -136,7 +226,7 @@ where
     ///
     /// For the meaning of "authority", refer to the documentation of [`Uri`](http::uri::Uri).
     ///
-    /// The `path` should implement [`PathRewriter`].
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
     ///
     /// # Errors
     ///
@@ -153,6 +243,7 @@ where
             scheme: Scheme::HTTPS,
             authority,
             path,
+            options: Options::default(),
         })
     }
 }
@@ -167,7 +258,7 @@ where
     ///
     /// For the meaning of "authority", refer to the documentation of [`Uri`](http::uri::Uri).
     ///
-    /// The `path` should implement [`PathRewriter`].
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
     #[cfg_attr(docsrs, doc(cfg(feature = "nativetls")))]
     /// # Errors
     ///
@@ -183,6 +274,7 @@ where
             scheme: Scheme::HTTPS,
             authority,
             path,
+            options: Options::default(),
         })
     }
 }
@@ -197,7 +289,7 @@ where
     ///
     /// For the meaning of "authority", refer to the documentation of [`Uri`](http::uri::Uri).
     ///
-    /// The `path` should implement [`PathRewriter`].
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
     #[cfg_attr(docsrs, doc(cfg(feature = "rustls")))]
     /// # Errors
     ///
@@ -213,17 +305,46 @@ where
             scheme: Scheme::HTTPS,
             authority,
             path,
+            options: Options::default(),
         })
     }
 }
 
+#[cfg(unix)]
+impl<Pr, B> OneshotService<Pr, UnixConnector, B>
+where
+    B: HttpBody + Send,
+    B::Data: Send,
+{
+    /// Use [`client::unix_default()`] to build a client.
+    ///
+    /// `path` is the Unix domain socket, and may be prefixed with `unix://`. The requests are sent
+    /// with the authority `localhost`, which is used as the `Host` header unless the request
+    /// already has one.
+    ///
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
+    #[cfg_attr(docsrs, doc(cfg(unix)))]
+    pub fn unix<P>(socket: P, path: Pr) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Self {
+            client: client::unix_default(socket),
+            scheme: Scheme::HTTP,
+            authority: Authority::from_static("localhost"),
+            path,
+            options: Options::default(),
+        }
+    }
+}
+
 impl<C, B, Pr> Service<Request<B>> for OneshotService<Pr, C, B>
 where
     C: Connect + Clone + Send + Sync + 'static,
     B: HttpBody + Send + 'static + Unpin,
     B::Data: Send,
     B::Error: Into<BoxErr>,
-    Pr: PathRewriter,
+    Pr: RequestRewriter,
 {
     type Response = Result<Response<Incoming>, Error>;
     type Error = Infallible;
@@ -240,6 +361,7 @@ where
             &self.scheme,
             &self.authority,
             &mut self.path,
+            &self.options,
         )
     }
 }
@@ -254,7 +376,7 @@ mod test {
 
     async fn make_svc() -> (
         ServerGuard,
-        OneshotService<ReplaceAll<'static>, HttpConnector, String>,
+        OneshotService<ReplaceAll<&'static str>, HttpConnector, String>,
     ) {
         let server = mockito::Server::new_async().await;
         let uri = Uri::try_from(&server.url());
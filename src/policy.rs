@@ -0,0 +1,316 @@
+//! A [`HostPolicy`] decides which hosts and ports may be reached through the proxy.
+//!
+//! It is used by `ForwardService` and `tunnel::Tunnel`. The names are checked as written in the
+//! requests, and the addresses they resolve to are checked against the denied IP addresses, by
+//! [`Resolver`] and the tunnel, so a name pointing to a denied address is denied as well.
+
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::uri::Authority;
+use hyper_util::client::legacy::connect::dns::{GaiResolver, Name};
+use tower_service::Service;
+
+/// Parses `part` of an IPv4 address as `inet_aton()` does, that is, in hexadecimal with `0x`, in
+/// octal with `0`, or in decimal.
+fn ipv4_part(part: &str) -> Option<u32> {
+    let (digits, radix) =
+        if let Some(hex) = part.strip_prefix("0x").or_else(|| part.strip_prefix("0X")) {
+            (hex, 16)
+        } else if part.len() > 1 && part.starts_with('0') {
+            (&part[1..], 8)
+        } else {
+            (part, 10)
+        };
+    if digits.is_empty() {
+        // `0x` alone is zero.
+        return (radix == 16).then_some(0);
+    }
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    u32::from_str_radix(digits, radix).ok()
+}
+
+/// Parses `host` as an IPv4 address in the shorthand forms accepted by `inet_aton()` and the
+/// resolvers, such as `127.1`, `2130706433` or `0x7f.0.0.1`.
+fn parse_ipv4(host: &str) -> Option<Ipv4Addr> {
+    let parts = host.split('.').map(ipv4_part).collect::<Option<Vec<_>>>()?;
+    let (last, init) = parts.split_last()?;
+    if init.len() > 3 || init.iter().any(|&part| part > 0xff) {
+        return None;
+    }
+    // The last part fills the remaining bytes.
+    let bits = 8 * (4 - init.len());
+    if bits < 32 && *last >> bits != 0 {
+        return None;
+    }
+    let init = init
+        .iter()
+        .enumerate()
+        .fold(0, |addr, (i, &part)| addr | part << (24 - 8 * i));
+    Some(Ipv4Addr::from(init | last))
+}
+
+/// Returns the canonical form of `host`: lowercase, without the brackets of an IPv6 address or a
+/// trailing dot, and with the IP addresses as [`IpAddr`] displays them.
+fn canonical(host: &str) -> String {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let host = host.strip_suffix('.').unwrap_or(host).to_ascii_lowercase();
+    match host.parse::<Ipv6Addr>() {
+        Ok(ip) => IpAddr::V6(ip).to_canonical().to_string(),
+        Err(_) => parse_ipv4(&host).map_or(host, |ip| ip.to_string()),
+    }
+}
+
+/// A rule matching a host and an optional port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    /// Either `*`, `*.suffix` or a host name or IP address, in the form of [`canonical()`].
+    host: String,
+    port: Option<u16>,
+}
+
+impl Rule {
+    fn new(host: &str, port: Option<u16>) -> Self {
+        Self {
+            host: canonical(host),
+            port,
+        }
+    }
+
+    fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|p| p != port) {
+            return false;
+        }
+        if self.host == "*" {
+            return true;
+        }
+        match self.host.strip_prefix("*.") {
+            Some(suffix) => host
+                .strip_suffix(suffix)
+                .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            None => self.host == host,
+        }
+    }
+}
+
+/// An allow and deny list of hosts and ports.
+///
+/// A host is an exact name or IP address, `*.example.com` for the subdomains of `example.com`
+/// (not including itself), or `*` for any host. A target is allowed if it matches any of the
+/// allowed rules and none of the denied rules, so nothing is allowed by default.
+///
+/// The hosts are compared case-insensitively, without a trailing dot, and the IP addresses by
+/// value, so `127.1` and `[::ffff:127.0.0.1]` are `127.0.0.1`.
+///
+/// ```
+/// # use axum_proxy::policy::HostPolicy;
+/// let policy = HostPolicy::new()
+///     .allow("*.example.com", Some(443))
+///     .deny("admin.example.com", None)
+///     .allow("10.0.0.1", None);
+///
+/// assert!(policy.is_allowed("api.example.com", 443));
+/// assert!(!policy.is_allowed("api.example.com", 80));
+/// assert!(!policy.is_allowed("example.com", 443));
+/// assert!(!policy.is_allowed("admin.example.com", 443));
+/// assert!(policy.is_allowed("10.0.0.1", 22));
+/// ```
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Default)]
+pub struct HostPolicy {
+    allowed: Vec<Rule>,
+    denied: Vec<Rule>,
+}
+
+impl HostPolicy {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows `host`, on `port` or on any port if `None`.
+    #[must_use]
+    pub fn allow(mut self, host: &str, port: Option<u16>) -> Self {
+        self.allowed.push(Rule::new(host, port));
+        self
+    }
+
+    /// Denies `host`, on `port` or on any port if `None`, even if it is allowed.
+    #[must_use]
+    pub fn deny(mut self, host: &str, port: Option<u16>) -> Self {
+        self.denied.push(Rule::new(host, port));
+        self
+    }
+
+    #[must_use]
+    pub fn is_allowed(&self, host: &str, port: u16) -> bool {
+        let host = canonical(host);
+        self.allowed.iter().any(|rule| rule.matches(&host, port))
+            && !self.denied.iter().any(|rule| rule.matches(&host, port))
+    }
+
+    /// Returns whether `ip`, an address a target resolves to, is not denied.
+    ///
+    /// The port is not known when resolving names for a client, so with `None`, an address
+    /// denied on some port only is denied on all ports.
+    #[must_use]
+    pub fn is_allowed_ip(&self, ip: IpAddr, port: Option<u16>) -> bool {
+        let host = ip.to_canonical().to_string();
+        !self.denied.iter().any(|rule| match port {
+            Some(port) => rule.matches(&host, port),
+            None => rule.matches(&host, rule.port.unwrap_or_default()),
+        })
+    }
+
+    /// Same as [`Self::is_allowed()`], with `default_port` if `authority` has none.
+    #[must_use]
+    pub fn is_allowed_authority(&self, authority: &Authority, default_port: u16) -> bool {
+        let port = authority.port_u16().unwrap_or(default_port);
+        self.is_allowed(authority.host(), port)
+    }
+}
+
+/// A resolver for [`HttpConnector`](crate::client::HttpConnector) failing when a name resolves
+/// to an address denied by the [`HostPolicy`].
+///
+/// Without this, a client may reach a denied address through a name that is allowed, or one
+/// changing its addresses between the check and the connection. The services fail such a request
+/// with [`Error::Forbidden`](crate::Error::Forbidden).
+///
+/// ```
+/// # use axum_proxy::client::{self, HttpConnector};
+/// # use axum_proxy::policy::{HostPolicy, Resolver};
+/// # use axum_proxy::ForwardService;
+/// # use http_body_util::Empty;
+/// # use hyper::body::Bytes;
+/// # use std::sync::Arc;
+/// let policy = HostPolicy::new().allow("*", None).deny("127.0.0.1", None);
+/// let connector = HttpConnector::new_with_resolver(Resolver::new(policy.clone()));
+/// let client = client::with_connector_default::<_, Empty<Bytes>>(connector);
+/// let svc = ForwardService::new(Arc::new(client), policy);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    policy: Arc<HostPolicy>,
+    inner: GaiResolver,
+}
+
+impl Resolver {
+    #[must_use]
+    pub fn new(policy: HostPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+            inner: GaiResolver::new(),
+        }
+    }
+}
+
+impl Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let policy = self.policy.clone();
+        let resolving = self.inner.call(name);
+        Box::pin(async move {
+            let addrs: Vec<_> = resolving.await?.collect();
+            match addrs
+                .iter()
+                .find(|addr| !policy.is_allowed_ip(addr.ip(), None))
+            {
+                Some(addr) => Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("Denied address: {}", addr.ip()),
+                )),
+                None => Ok(addrs.into_iter()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wildcard() {
+        let policy = HostPolicy::new().allow("*.Example.com", None);
+        assert!(policy.is_allowed("a.example.com", 1));
+        assert!(policy.is_allowed("a.b.EXAMPLE.com", 1));
+        assert!(!policy.is_allowed("example.com", 1));
+        assert!(!policy.is_allowed("aexample.com", 1));
+        assert!(!policy.is_allowed(".example.com", 1));
+
+        let policy = HostPolicy::new().allow("*", Some(443));
+        assert!(policy.is_allowed("anything", 443));
+        assert!(!policy.is_allowed("anything", 80));
+
+        let policy = HostPolicy::new()
+            .allow("*", None)
+            .deny("*.internal", Some(22));
+        assert!(policy.is_allowed("host.internal", 80));
+        assert!(!policy.is_allowed("host.internal", 22));
+        assert!(policy.is_allowed("internal", 22));
+    }
+
+    #[test]
+    fn authority() {
+        let policy = HostPolicy::new()
+            .allow("[::1]", Some(80))
+            .allow("localhost", Some(8080));
+        let authority = |s| Authority::from_static(s);
+        assert!(policy.is_allowed_authority(&authority("[::1]"), 80));
+        assert!(!policy.is_allowed_authority(&authority("[::1]:81"), 80));
+        assert!(policy.is_allowed_authority(&authority("localhost:8080"), 80));
+        assert!(!policy.is_allowed_authority(&authority("localhost"), 80));
+        assert!(!HostPolicy::new().is_allowed("localhost", 80));
+    }
+
+    #[test]
+    fn canonical() {
+        let policy = HostPolicy::new()
+            .allow("*", None)
+            .deny("Internal.example.com.", None)
+            .deny("127.0.0.1", None)
+            .deny("[0:0::1]", Some(22));
+        for host in [
+            "internal.example.com.",
+            "INTERNAL.example.com",
+            "127.1",
+            "127.0.1",
+            "2130706433",
+            "0x7f.0.0.1",
+            "0x7F000001",
+            "0177.0.0.1",
+            "127.0.0.1.",
+            "[::ffff:127.0.0.1]",
+            "[::ffff:7f00:1]",
+        ] {
+            assert!(!policy.is_allowed(host, 80), "{host}");
+        }
+        assert!(!policy.is_allowed("[::1]", 22));
+        assert!(policy.is_allowed("[::1]", 80));
+        assert!(policy.is_allowed("127.0.0.2", 80));
+        assert!(policy.is_allowed("127.0.0.256", 80));
+        assert!(policy.is_allowed("256.0.0.1", 80));
+        assert!(policy.is_allowed("0x7g.0.0.1", 80));
+        assert!(policy.is_allowed("08.0.0.1", 80));
+
+        assert!(!policy.is_allowed_ip("127.0.0.1".parse().unwrap(), Some(80)));
+        assert!(!policy.is_allowed_ip("::ffff:127.0.0.1".parse().unwrap(), None));
+        assert!(!policy.is_allowed_ip("::1".parse().unwrap(), None));
+        assert!(policy.is_allowed_ip("::1".parse().unwrap(), Some(80)));
+        assert!(policy.is_allowed_ip("10.0.0.1".parse().unwrap(), None));
+    }
+}
@@ -0,0 +1,838 @@
+//! Sends and accepts the
+//! [PROXY protocol](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt) header, carrying
+//! the address of the client.
+//!
+//! # Toward upstreams
+//!
+//! [`ProxyProtocolConnector`] writes the header at the start of each connection it makes. Since
+//! the header describes one client, a connection to an upstream cannot be shared by the clients,
+//! and neither can the pool of a [`Client`]. [`ProxyProtocolService`] makes this explicit: it keeps
+//! a [`Client`] for each client connection, identified by the address in the
+//! [`ConnectInfo<SocketAddr>`] of the request, and drops it after the client has been idle for a
+//! while. So the number of connections to the upstream grows with the number of clients.
+//!
+//! ```no_run
+//! use std::net::SocketAddr;
+//!
+//! use axum::Router;
+//! use axum_proxy::proxy_protocol::{ProxyProtocolService, Version};
+//! use axum_proxy::Identity;
+//!
+//! # async fn run() {
+//! let svc = ProxyProtocolService::http_default(Version::V2, "backend:8080", Identity).unwrap();
+//! let app = Router::new().route_service("/{*path}", svc);
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+//! axum::serve(
+//!     listener,
+//!     app.into_make_service_with_connect_info::<SocketAddr>(),
+//! )
+//! .await
+//! .unwrap();
+//! # }
+//! ```
+//!
+//! # From a load balancer
+//!
+//! [`ProxyProtocolListener`] reads the header of each connection accepted by a [`Listener`]
+//! before handing it to `axum::serve`, and exposes the address of the client as
+//! [`ConnectInfo<ClientAddr>`], which [`ProxyProtocolService`] understands as well.
+//!
+//! ```no_run
+//! use axum::extract::ConnectInfo;
+//! use axum::routing::get;
+//! use axum::Router;
+//! use axum_proxy::proxy_protocol::{ClientAddr, ProxyProtocolListener};
+//!
+//! # async fn run() {
+//! let app = Router::new().route(
+//!     "/",
+//!     get(|ConnectInfo(ClientAddr(addr)): ConnectInfo<ClientAddr>| async move {
+//!         addr.to_string()
+//!     }),
+//! );
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+//! axum::serve(
+//!     ProxyProtocolListener::new(listener),
+//!     app.into_make_service_with_connect_info::<ClientAddr>(),
+//! )
+//! .await
+//! .unwrap();
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::extract::connect_info::Connected;
+use axum::extract::ConnectInfo;
+use axum::serve::{IncomingStream, Listener};
+use http::uri::{Authority, Scheme, Uri};
+use http::{Error as HttpError, Request, Response, Version as HttpVersion};
+use hyper::body::{Body as HttpBody, Incoming};
+use hyper::rt::{Read, Write};
+use hyper_util::client::legacy::connect::Connect;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tower_service::Service;
+
+use crate::client::HttpConnector;
+use crate::future::{Options, RevProxyFuture};
+use crate::rewrite::{HeaderRewriter, RequestRewriter};
+use crate::Error;
+
+type BoxErr = Box<dyn std::error::Error + Send + Sync>;
+
+/// The signature at the start of a version 2 header.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The version of the PROXY protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// The human-readable header, *e.g.* `PROXY TCP4 192.0.2.1 192.0.2.2 56324 443`.
+    V1,
+    /// The binary header.
+    V2,
+}
+
+impl Version {
+    /// Encodes a header for a connection from `source` to `destination`.
+    ///
+    /// If the address families differ, `destination` is replaced with the unspecified address of
+    /// the family of `source`.
+    pub(crate) fn encode(self, source: SocketAddr, destination: SocketAddr) -> Vec<u8> {
+        let destination = match (source, destination) {
+            (SocketAddr::V4(_), SocketAddr::V6(_)) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            (SocketAddr::V6(_), SocketAddr::V4(_)) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+            _ => destination,
+        };
+
+        match self {
+            Self::V1 => {
+                let family = if source.is_ipv4() { "TCP4" } else { "TCP6" };
+                format!(
+                    "PROXY {family} {} {} {} {}\r\n",
+                    source.ip(),
+                    destination.ip(),
+                    source.port(),
+                    destination.port()
+                )
+                .into_bytes()
+            },
+            Self::V2 => {
+                let mut header = V2_SIGNATURE.to_vec();
+                // version 2, PROXY command
+                header.push(0x21);
+                match (source.ip(), destination.ip()) {
+                    (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                        // TCP over IPv4
+                        header.extend_from_slice(&[0x11, 0, 12]);
+                        header.extend_from_slice(&src.octets());
+                        header.extend_from_slice(&dst.octets());
+                    },
+                    (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                        // TCP over IPv6
+                        header.extend_from_slice(&[0x21, 0, 36]);
+                        header.extend_from_slice(&src.octets());
+                        header.extend_from_slice(&dst.octets());
+                    },
+                    _ => unreachable!("the families are the same"),
+                }
+                header.extend_from_slice(&source.port().to_be_bytes());
+                header.extend_from_slice(&destination.port().to_be_bytes());
+                header
+            },
+        }
+    }
+}
+
+/// A connector writing a PROXY protocol header at the start of each connection.
+///
+/// All the connections carry the same addresses, so a [`Client`] with this connector must serve
+/// only one client. See the [module documentation](self).
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug, Clone)]
+pub struct ProxyProtocolConnector<C = HttpConnector> {
+    inner: C,
+    header: Arc<[u8]>,
+}
+
+impl<C> ProxyProtocolConnector<C> {
+    /// Connects by `inner`, and tells that the connection is from `source` to `destination`.
+    pub fn new(inner: C, version: Version, source: SocketAddr, destination: SocketAddr) -> Self {
+        Self {
+            inner,
+            header: version.encode(source, destination).into(),
+        }
+    }
+}
+
+impl<C> Service<Uri> for ProxyProtocolConnector<C>
+where
+    C: Service<Uri> + Send,
+    C::Response: Read + Write + Unpin + Send + 'static,
+    C::Future: Send + 'static,
+    C::Error: Into<BoxErr>,
+{
+    type Response = C::Response;
+    type Error = BoxErr;
+    type Future = Pin<Box<dyn Future<Output = Result<C::Response, BoxErr>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting = self.inner.call(dst);
+        let header = self.header.clone();
+        Box::pin(async move {
+            let mut io = TokioIo::new(connecting.await.map_err(Into::into)?);
+            io.write_all(&header).await?;
+            Ok(io.into_inner())
+        })
+    }
+}
+
+/// The [`Client`]s of [`ProxyProtocolService`] for each client connection.
+struct Clients<C, B> {
+    clients: HashMap<SocketAddr, (Client<ProxyProtocolConnector<C>, B>, Instant)>,
+    swept: Instant,
+}
+
+/// A [`Service<Request<B>>`] that sends a request with a PROXY protocol header, owning a
+/// [`Client`] for each client connection.
+///
+/// The client address is taken from the [`ConnectInfo<SocketAddr>`] or [`ConnectInfo<ClientAddr>`]
+/// extension, so the app must be served with
+/// [`into_make_service_with_connect_info()`](axum::Router::into_make_service_with_connect_info).
+/// Without it, the service returns [`Error::NoClientAddr`].
+///
+/// The connections to the upstream are not shared by the clients. The [`Client`] of a client is
+/// dropped, closing its connections, after [`Self::idle_timeout()`] without requests.
+#[expect(clippy::module_name_repetitions)]
+pub struct ProxyProtocolService<Pr, C = HttpConnector, B = Incoming> {
+    connector: C,
+    version: Version,
+    destination: Option<SocketAddr>,
+    idle_timeout: Duration,
+    clients: Arc<Mutex<Clients<C, B>>>,
+    scheme: Scheme,
+    authority: Authority,
+    path: Pr,
+    options: Options,
+}
+
+impl<Pr: Clone, C: Clone, B> Clone for ProxyProtocolService<Pr, C, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            connector: self.connector.clone(),
+            version: self.version,
+            destination: self.destination,
+            idle_timeout: self.idle_timeout,
+            clients: self.clients.clone(),
+            scheme: self.scheme.clone(),
+            authority: self.authority.clone(),
+            path: self.path.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<Pr, C, B> ProxyProtocolService<Pr, C, B> {
+    /// Initializes a service, connecting to the upstream by `connector`.
+    ///
+    /// For the meaning of "scheme" and "authority", refer to the documentation of
+    /// [`Uri`](http::uri::Uri).
+    ///
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
+    ///
+    /// # Errors
+    ///
+    /// When `scheme` or `authority` cannot be converted into a [`Scheme`] or [`Authority`].
+    pub fn from<S, A>(
+        connector: C,
+        version: Version,
+        scheme: S,
+        authority: A,
+        path: Pr,
+    ) -> Result<Self, HttpError>
+    where
+        Scheme: TryFrom<S>,
+        <Scheme as TryFrom<S>>::Error: Into<HttpError>,
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<HttpError>,
+    {
+        let scheme = scheme.try_into().map_err(Into::into)?;
+        let authority = authority.try_into().map_err(Into::into)?;
+        Ok(Self {
+            connector,
+            version,
+            destination: None,
+            idle_timeout: Duration::from_secs(90),
+            clients: Arc::new(Mutex::new(Clients {
+                clients: HashMap::new(),
+                swept: Instant::now(),
+            })),
+            scheme,
+            authority,
+            path,
+            options: Options::default(),
+        })
+    }
+
+    /// Sets the destination address in the headers.
+    ///
+    /// By default, this is the unspecified address with the port `0`.
+    #[must_use]
+    pub fn destination(mut self, destination: SocketAddr) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+
+    /// Sets how long the [`Client`] of a client is kept without requests, 90 seconds by default.
+    #[must_use]
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Sets the HTTP version of the requests to the upstreams.
+    ///
+    /// See [`ReusedService::upstream_version()`](crate::ReusedService::upstream_version).
+    #[must_use]
+    pub fn upstream_version(mut self, version: HttpVersion) -> Self {
+        self.options.version = Some(version);
+        self
+    }
+
+    /// Limits the size of the request bodies.
+    ///
+    /// See [`ReusedService::max_body_size()`](crate::ReusedService::max_body_size).
+    #[must_use]
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.options.max_body_size = Some(limit);
+        self
+    }
+
+    /// Rewrites the headers, and the method, of the requests.
+    ///
+    /// See [`ReusedService::request_headers()`](crate::ReusedService::request_headers).
+    #[must_use]
+    pub fn request_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.request_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Rewrites the headers of the responses.
+    ///
+    /// See [`ReusedService::response_headers()`](crate::ReusedService::response_headers).
+    #[must_use]
+    pub fn response_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.response_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Switches to gRPC mode.
+    ///
+    /// See [`ReusedService::grpc()`](crate::ReusedService::grpc).
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    #[must_use]
+    pub fn grpc(mut self) -> Self {
+        self.options.grpc();
+        self
+    }
+}
+
+impl<Pr, B> ProxyProtocolService<Pr, HttpConnector, B> {
+    /// Connects to the upstream by an [`HttpConnector`].
+    ///
+    /// For the meaning of "authority", refer to the documentation of [`Uri`](http::uri::Uri).
+    ///
+    /// The `path` should implement [`PathRewriter`](crate::PathRewriter) or [`RequestRewriter`].
+    ///
+    /// # Errors
+    ///
+    /// When `authority` cannot be converted into an [`Authority`].
+    pub fn http_default<A>(version: Version, authority: A, path: Pr) -> Result<Self, HttpError>
+    where
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<HttpError>,
+    {
+        Self::from(HttpConnector::new(), version, Scheme::HTTP, authority, path)
+    }
+}
+
+impl<Pr, C, B> ProxyProtocolService<Pr, C, B>
+where
+    C: Clone,
+    ProxyProtocolConnector<C>: Connect + Clone,
+    B: HttpBody + Send,
+    B::Data: Send,
+{
+    /// The [`Client`] for `source`, sweeping the idle ones.
+    fn client(&self, source: SocketAddr) -> Client<ProxyProtocolConnector<C>, B> {
+        let now = Instant::now();
+        let mut clients = self
+            .clients
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if now.duration_since(clients.swept) >= self.idle_timeout {
+            let idle_timeout = self.idle_timeout;
+            clients
+                .clients
+                .retain(|_, (_, used)| now.duration_since(*used) < idle_timeout);
+            clients.swept = now;
+        }
+
+        let (client, used) = clients.clients.entry(source).or_insert_with(|| {
+            let destination = self.destination.unwrap_or_else(|| match source {
+                SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+                SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+            });
+            let connector = ProxyProtocolConnector::new(
+                self.connector.clone(),
+                self.version,
+                source,
+                destination,
+            );
+            let client =
+                hyper_util::client::legacy::Builder::new(hyper_util::rt::TokioExecutor::new())
+                    .pool_idle_timeout(self.idle_timeout)
+                    .build(connector);
+            (client, now)
+        });
+        *used = now;
+        client.clone()
+    }
+}
+
+impl<C, B, Pr> Service<Request<B>> for ProxyProtocolService<Pr, C, B>
+where
+    C: Clone,
+    ProxyProtocolConnector<C>: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static + Unpin,
+    B::Data: Send,
+    B::Error: Into<BoxErr>,
+    Pr: RequestRewriter,
+{
+    type Response = Result<Response<Incoming>, Error>;
+    type Error = Infallible;
+    type Future = RevProxyFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let extensions = req.extensions();
+        let source = extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr)
+            .or_else(|| {
+                extensions
+                    .get::<ConnectInfo<ClientAddr>>()
+                    .map(|ConnectInfo(ClientAddr(addr))| *addr)
+            });
+        let Some(source) = source else {
+            return RevProxyFuture::error(Error::NoClientAddr, &self.options);
+        };
+        let client = self.client(source);
+        RevProxyFuture::new(
+            &client,
+            req,
+            &self.scheme,
+            &self.authority,
+            &mut self.path,
+            &self.options,
+        )
+    }
+}
+
+/// The longest version 1 header, including the CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Reads a PROXY protocol header from `io`, returning the source address in it.
+///
+/// If the header does not carry the source address, *e.g.* for health checks of the load
+/// balancer, `peer` is returned. Nothing after the header is read.
+async fn read_header<T>(io: &mut T, peer: SocketAddr) -> Result<SocketAddr, IoError>
+where
+    T: AsyncRead + Unpin,
+{
+    let invalid = |message: &str| IoError::new(ErrorKind::InvalidData, message.to_owned());
+
+    let mut header = [0; 16];
+    io.read_exact(&mut header[..12]).await?;
+
+    if header[..12] == V2_SIGNATURE {
+        io.read_exact(&mut header[12..]).await?;
+        let (version, command) = (header[12] >> 4, header[12] & 0x0f);
+        if version != 2 {
+            return Err(invalid("unsupported PROXY protocol version"));
+        }
+        let mut addresses = vec![0; usize::from(u16::from_be_bytes([header[14], header[15]]))];
+        io.read_exact(&mut addresses).await?;
+
+        match command {
+            // LOCAL
+            0 => return Ok(peer),
+            // PROXY
+            1 => {},
+            _ => return Err(invalid("unsupported PROXY protocol command")),
+        }
+        let source = match (header[13] >> 4, addresses.len()) {
+            (1, 12..) => {
+                let ip: [u8; 4] = addresses[..4].try_into().expect("4 bytes");
+                let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+                (Ipv4Addr::from(ip), port).into()
+            },
+            (2, 36..) => {
+                let ip: [u8; 16] = addresses[..16].try_into().expect("16 bytes");
+                let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+                (Ipv6Addr::from(ip), port).into()
+            },
+            // UNSPEC or UNIX
+            (0 | 3, _) => peer,
+            _ => return Err(invalid("invalid PROXY protocol addresses")),
+        };
+        return Ok(source);
+    }
+
+    if !header.starts_with(b"PROXY ") {
+        return Err(invalid("no PROXY protocol header"));
+    }
+    let mut line = header[..12].to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("too long PROXY protocol header"));
+        }
+        line.push(io.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("invalid PROXY protocol header"))?;
+    let fields: Vec<_> = line.split(' ').collect();
+    match fields[..] {
+        ["PROXY", "UNKNOWN", ..] => Ok(peer),
+        ["PROXY", "TCP4" | "TCP6", source, _, port, _] => {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| invalid("invalid source address"))?;
+            let port: u16 = port.parse().map_err(|_| invalid("invalid source port"))?;
+            Ok((ip, port).into())
+        },
+        _ => Err(invalid("invalid PROXY protocol header")),
+    }
+}
+
+/// The address of a client, which is the source address in the PROXY protocol header.
+///
+/// This implements [`Connected`] for the connections from [`ProxyProtocolListener`], so it can be
+/// extracted by [`ConnectInfo<ClientAddr>`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientAddr(pub SocketAddr);
+
+impl From<ClientAddr> for SocketAddr {
+    fn from(addr: ClientAddr) -> Self {
+        addr.0
+    }
+}
+
+impl<L> Connected<IncomingStream<'_, ProxyProtocolListener<L>>> for ClientAddr
+where
+    L: Listener<Addr = SocketAddr>,
+{
+    fn connect_info(stream: IncomingStream<'_, ProxyProtocolListener<L>>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+/// A [`Listener`] reading the PROXY protocol header of each connection, version 1 or 2.
+///
+/// The address of an accepted connection is the source address in the header. The headers are
+/// read in background tasks, so a slow client does not block the others, and the connections
+/// without a valid header within [`Self::header_timeout()`] are closed.
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct ProxyProtocolListener<L: Listener> {
+    inner: Option<L>,
+    local_addr: Result<SocketAddr, ErrorKind>,
+    header_timeout: Duration,
+    sender: mpsc::Sender<(L::Io, SocketAddr)>,
+    accepted: mpsc::Receiver<(L::Io, SocketAddr)>,
+}
+
+impl<L> ProxyProtocolListener<L>
+where
+    L: Listener<Addr = SocketAddr>,
+{
+    pub fn new(inner: L) -> Self {
+        let (sender, accepted) = mpsc::channel(64);
+        Self {
+            local_addr: inner.local_addr().map_err(|e| e.kind()),
+            inner: Some(inner),
+            header_timeout: Duration::from_secs(5),
+            sender,
+            accepted,
+        }
+    }
+
+    /// Sets how long to wait for the header of a connection, 5 seconds by default.
+    #[must_use]
+    pub fn header_timeout(mut self, timeout: Duration) -> Self {
+        self.header_timeout = timeout;
+        self
+    }
+
+    /// Accepts the connections of `inner` until the listener is dropped.
+    async fn run(mut inner: L, sender: mpsc::Sender<(L::Io, SocketAddr)>, timeout: Duration) {
+        loop {
+            let (mut io, peer) = tokio::select! {
+                () = sender.closed() => return,
+                accepted = inner.accept() => accepted,
+            };
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(timeout, read_header(&mut io, peer)).await {
+                    Ok(Ok(addr)) => {
+                        if sender.send((io, addr)).await.is_err() {
+                            log::debug!("{peer}: The listener has been dropped");
+                        }
+                    },
+                    Ok(Err(e)) => log::warn!("{peer}: {e}"),
+                    Err(_) => log::warn!("{peer}: Timed out reading the PROXY protocol header"),
+                }
+            });
+        }
+    }
+}
+
+impl<L> Listener for ProxyProtocolListener<L>
+where
+    L: Listener<Addr = SocketAddr>,
+{
+    type Io = L::Io;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        if let Some(inner) = self.inner.take() {
+            tokio::spawn(Self::run(inner, self.sender.clone(), self.header_timeout));
+        }
+        match self.accepted.recv().await {
+            Some(accepted) => accepted,
+            // Unreachable, as `self` holds a sender.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> Result<Self::Addr, IoError> {
+        self.local_addr.map_err(IoError::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::ReplaceAll;
+
+    #[test]
+    fn encode() {
+        let source = "192.0.2.1:56324".parse().unwrap();
+        let destination = "192.0.2.2:443".parse().unwrap();
+        assert_eq!(
+            Version::V1.encode(source, destination),
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 56324 443\r\n"
+        );
+
+        let mut v2 = V2_SIGNATURE.to_vec();
+        v2.extend_from_slice(&[
+            0x21, 0x11, 0, 12, 192, 0, 2, 1, 192, 0, 2, 2, 0xdc, 0x04, 1, 0xbb,
+        ]);
+        assert_eq!(Version::V2.encode(source, destination), v2);
+
+        let source = "[2001:db8::1]:1234".parse().unwrap();
+        assert_eq!(
+            Version::V1.encode(source, destination),
+            b"PROXY TCP6 2001:db8::1 :: 1234 0\r\n"
+        );
+        assert_eq!(Version::V2.encode(source, destination).len(), 16 + 36);
+    }
+
+    #[tokio::test]
+    async fn per_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    let mut header = String::new();
+                    stream.read_line(&mut header).await.unwrap();
+                    let body = header.trim_end().to_owned();
+                    loop {
+                        let mut head = Vec::new();
+                        while !head.ends_with(b"\r\n\r\n") {
+                            match stream.read_u8().await {
+                                Ok(b) => head.push(b),
+                                Err(_) => return,
+                            }
+                        }
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{body}",
+                            body.len()
+                        );
+                        stream
+                            .get_mut()
+                            .write_all(response.as_bytes())
+                            .await
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let mut svc = ProxyProtocolService::from(
+            HttpConnector::new(),
+            Version::V1,
+            "http",
+            authority,
+            ReplaceAll("foo", "goo"),
+        )
+        .unwrap();
+        let destination: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        svc = svc
+            .destination(destination)
+            .response_headers(HeaderRewriter::new().set(
+                http::HeaderName::from_static("x-proxied"),
+                http::HeaderValue::from_static("1"),
+            ));
+
+        for (source, expected) in [
+            ("10.0.0.1:1000", "PROXY TCP4 10.0.0.1 127.0.0.1 1000 80"),
+            ("10.0.0.2:2000", "PROXY TCP4 10.0.0.2 127.0.0.1 2000 80"),
+            ("10.0.0.1:1000", "PROXY TCP4 10.0.0.1 127.0.0.1 1000 80"),
+        ] {
+            let source: SocketAddr = source.parse().unwrap();
+            let mut request = Request::get("/foo").body(String::new()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(source));
+            let response = svc.call(request).await.unwrap().unwrap();
+            assert_eq!(response.headers()["x-proxied"], "1");
+            let body = http_body_util::BodyExt::collect(response.into_body())
+                .await
+                .unwrap()
+                .to_bytes();
+            assert_eq!(body, expected);
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+        let request = Request::get("/foo").body(String::new()).unwrap();
+        let result = svc.call(request).await.unwrap();
+        assert!(matches!(result, Err(Error::NoClientAddr)));
+    }
+
+    #[tokio::test]
+    async fn parse() {
+        let peer = "127.0.0.1:1".parse().unwrap();
+        let destination = "192.0.2.2:443".parse().unwrap();
+        for source in ["192.0.2.1:56324", "[2001:db8::1]:1234"] {
+            let source = source.parse().unwrap();
+            for version in [Version::V1, Version::V2] {
+                let mut data = version.encode(source, destination);
+                data.extend_from_slice(b"GET");
+                let mut io = &data[..];
+                assert_eq!(read_header(&mut io, peer).await.unwrap(), source);
+                assert_eq!(io, b"GET");
+            }
+        }
+
+        let mut local = V2_SIGNATURE.to_vec();
+        local.extend_from_slice(&[0x20, 0, 0, 0]);
+        for data in [&b"PROXY UNKNOWN\r\n"[..], &local] {
+            assert_eq!(read_header(&mut &data[..], peer).await.unwrap(), peer);
+        }
+
+        for data in [
+            &b"GET / HTTP/1.1\r\n\r\n"[..],
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 56324\r\n",
+            b"PROXY TCP4 192.0.2.1 192.0.2.2 56324 443",
+            &[b"PROXY TCP4 ".as_slice(), &[b'1'; 200]].concat(),
+            &[V2_SIGNATURE.as_slice(), &[0x22, 0x11, 0, 12], &[0; 12]].concat(),
+        ] {
+            assert!(read_header(&mut &data[..], peer).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn listener() {
+        use axum::routing::get;
+        use axum::Router;
+        use tokio::net::TcpStream;
+
+        let app =
+            Router::new().route(
+                "/",
+                get(
+                    |ConnectInfo(ClientAddr(addr)): ConnectInfo<ClientAddr>| async move {
+                        addr.to_string()
+                    },
+                ),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener = ProxyProtocolListener::new(listener).header_timeout(Duration::from_secs(1));
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<ClientAddr>(),
+            )
+            .await
+            .unwrap();
+        });
+
+        // A connection stalled in the header does not block the others.
+        let _stalled = TcpStream::connect(addr).await.unwrap();
+
+        let source = "10.0.0.1:1000".parse().unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(&Version::V2.encode(source, addr))
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: a\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("10.0.0.1:1000"), "{response}");
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        // Closed, possibly with a reset as the request has not been read.
+        let mut response = Vec::new();
+        let result = stream.read_to_end(&mut response).await;
+        assert!(result.is_err() || response.is_empty());
+    }
+}
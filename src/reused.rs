@@ -5,8 +5,10 @@ use std::task::{Context, Poll};
 use client::HttpConnector;
 #[cfg(feature = "__rustls")]
 use client::RustlsConnector;
+#[cfg(unix)]
+use client::UnixConnector;
 use http::uri::{Authority, Scheme};
-use http::{Error as HttpError, Request, Response};
+use http::{Error as HttpError, Request, Response, Version};
 use hyper::body::{Body as HttpBody, Incoming};
 #[cfg(feature = "nativetls")]
 use hyper_tls::HttpsConnector as NativeTlsConnector;
@@ -14,8 +16,8 @@ use hyper_util::client::legacy::connect::Connect;
 use hyper_util::client::legacy::Client;
 use tower_service::Service;
 
-use crate::future::RevProxyFuture;
-use crate::rewrite::PathRewriter;
+use crate::future::{Options, RevProxyFuture};
+use crate::rewrite::{HeaderRewriter, RequestRewriter};
 use crate::{client, Error};
 
 type BoxErr = Box<dyn std::error::Error + Send + Sync>;
@@ -26,6 +28,7 @@ pub struct Builder<C = HttpConnector, B = Incoming> {
     client: Arc<Client<C, B>>,
     scheme: Scheme,
     authority: Authority,
+    options: Options,
 }
 
 impl<C, B> Clone for Builder<C, B> {
@@ -34,6 +37,7 @@ impl<C, B> Clone for Builder<C, B> {
             client: self.client.clone(),
             scheme: self.scheme.clone(),
             authority: self.authority.clone(),
+            options: self.options.clone(),
         }
     }
 }
@@ -44,14 +48,55 @@ impl<C, B> Builder<C, B> {
             client,
             scheme,
             authority,
+            options,
         } = Clone::clone(self);
         ReusedService {
             client,
             scheme,
             authority,
             path,
+            options,
         }
     }
+
+    /// Sets [`ReusedService::upstream_version()`] of the services built by this builder.
+    #[must_use]
+    pub fn upstream_version(mut self, version: Version) -> Self {
+        self.options.version = Some(version);
+        self
+    }
+
+    /// Sets [`ReusedService::max_body_size()`] of the services built by this builder.
+    #[must_use]
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.options.max_body_size = Some(limit);
+        self
+    }
+
+    /// Sets [`ReusedService::request_headers()`] of the services built by this builder.
+    #[must_use]
+    pub fn request_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.request_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Sets [`ReusedService::response_headers()`] of the services built by this builder.
+    #[must_use]
+    pub fn response_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.response_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Switches the services built by this builder to gRPC mode.
+    ///
+    /// See [`ReusedService::grpc()`].
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    #[must_use]
+    pub fn grpc(mut self) -> Self {
+        self.options.grpc();
+        self
+    }
 }
 
 /// Builder of [`ReusedService`], with [`client::http_default()`].
@@ -71,6 +116,25 @@ where
     builder(client::http_default(), Scheme::HTTP, authority)
 }
 
+/// Builder of [`ReusedService`], with [`client::h2c_default()`].
+///
+/// For the meaning of "authority", refer to the documentation of [`Uri`](http::uri::Uri).
+///
+/// # Errors
+///
+/// When `authority` cannot be converted into an [`Authority`].
+#[cfg(feature = "http2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+pub fn builder_h2c<B, A>(authority: A) -> Result<Builder<HttpConnector, B>, HttpError>
+where
+    B: HttpBody + Send,
+    B::Data: Send,
+    Authority: TryFrom<A>,
+    <Authority as TryFrom<A>>::Error: Into<HttpError>,
+{
+    builder(client::h2c_default(), Scheme::HTTP, authority)
+}
+
 /// Builder of [`ReusedService`], with [`client::https_default()`].
 ///
 /// This is the same as [`builder_nativetls()`].
@@ -136,6 +200,27 @@ where
     builder(client::rustls_default(), Scheme::HTTPS, authority)
 }
 
+/// Builder of [`ReusedService`], with [`client::unix_default()`].
+///
+/// `path` is the Unix domain socket, and may be prefixed with `unix://`. The requests are sent
+/// with the authority `localhost`, which is used as the `Host` header unless the request already
+/// has one.
+#[cfg(unix)]
+#[cfg_attr(docsrs, doc(cfg(unix)))]
+pub fn builder_unix<B, P>(path: P) -> Builder<UnixConnector, B>
+where
+    B: HttpBody + Send,
+    B::Data: Send,
+    P: AsRef<std::path::Path>,
+{
+    Builder {
+        client: Arc::new(client::unix_default(path)),
+        scheme: Scheme::HTTP,
+        authority: Authority::from_static("localhost"),
+        options: Options::default(),
+    }
+}
+
 /// Builder of [`ReusedService`].
 ///
 /// For the meaning of "scheme" and "authority", refer to the documentation of
@@ -161,6 +246,7 @@ where
         client: Arc::new(client),
         scheme,
         authority,
+        options: Options::default(),
     })
 }
 
@@ -201,6 +287,7 @@ pub struct ReusedService<Pr, C, B = Incoming> {
     scheme: Scheme,
     authority: Authority,
     path: Pr,
+    options: Options,
 }
 
 impl<Pr: Clone, C, B> Clone for ReusedService<Pr, C, B> {
@@ -211,6 +298,7 @@ impl<Pr: Clone, C, B> Clone for ReusedService<Pr, C, B> {
             scheme: self.scheme.clone(),
             authority: self.authority.clone(),
             path: self.path.clone(),
+            options: self.options.clone(),
         }
     }
 }
@@ -238,8 +326,63 @@ impl<Pr, C, B> ReusedService<Pr, C, B> {
             scheme,
             authority,
             path,
+            options: Options::default(),
         })
     }
+
+    /// Sets the HTTP version of the requests to the upstream.
+    ///
+    /// By default, the version of the incoming request is kept as is, so that, for example, an
+    /// HTTP/2 request cannot be sent to an upstream only speaking HTTP/1.1. The version must be
+    /// supported by the [`Client`].
+    #[must_use]
+    pub fn upstream_version(mut self, version: Version) -> Self {
+        self.options.version = Some(version);
+        self
+    }
+
+    /// Limits the size of the request bodies to `limit` bytes.
+    ///
+    /// A request with a larger `Content-Length` or size hint is rejected with
+    /// [`Error::PayloadTooLarge`](crate::Error::PayloadTooLarge) before it is sent. The bodies
+    /// without a known length, such as chunked ones, must be wrapped with
+    /// [`LimitedBody`](crate::LimitedBody) to be limited while they are streamed.
+    #[must_use]
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.options.max_body_size = Some(limit);
+        self
+    }
+
+    /// Rewrites the headers, and the method, of the requests with `rewriter` before sending them,
+    /// after their path is rewritten.
+    #[must_use]
+    pub fn request_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.request_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Rewrites the headers of the responses with `rewriter`.
+    #[must_use]
+    pub fn response_headers(mut self, rewriter: HeaderRewriter) -> Self {
+        self.options.response_headers = Some(Arc::new(rewriter));
+        self
+    }
+
+    /// Switches to gRPC mode.
+    ///
+    /// The requests are sent over HTTP/2, so the [`Client`] must speak it, e.g.
+    /// [`client::h2c_default()`](crate::client::h2c_default) for plain-text upstreams. The header
+    /// `te: trailers` is set, and the trailers of the responses are passed through as is. If the
+    /// request has a `grpc-timeout` header, the upstream must send the response headers within it;
+    /// the body is not bounded by this deadline. Errors are returned as
+    /// [`Error::Grpc`](crate::Error::Grpc), rendered as trailers-only gRPC responses.
+    #[cfg(feature = "http2")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "http2")))]
+    #[must_use]
+    pub fn grpc(mut self) -> Self {
+        self.options.grpc();
+        self
+    }
 }
 
 impl<B, Pr> ReusedService<Pr, HttpConnector, B>
@@ -265,6 +408,7 @@ where
             scheme: Scheme::HTTP,
             authority,
             path,
+            options: Options::default(),
         })
     }
 }
@@ -296,6 +440,7 @@ where
             scheme: Scheme::HTTPS,
             authority,
             path,
+            options: Options::default(),
         })
     }
 }
@@ -326,6 +471,7 @@ where
             scheme: Scheme::HTTPS,
             authority,
             path,
+            options: Options::default(),
         })
     }
 }
@@ -356,6 +502,7 @@ where
             scheme: Scheme::HTTPS,
             authority,
             path,
+            options: Options::default(),
         })
     }
 }
@@ -366,7 +513,7 @@ where
     B: HttpBody + Send + 'static + Unpin,
     B::Data: Send,
     B::Error: Into<BoxErr>,
-    Pr: PathRewriter,
+    Pr: RequestRewriter,
 {
     type Response = Result<Response<Incoming>, Error>;
     type Error = Infallible;
@@ -383,6 +530,7 @@ where
             &self.scheme,
             &self.authority,
             &mut self.path,
+            &self.options,
         )
     }
 }
@@ -393,11 +541,14 @@ mod test {
     use mockito::ServerGuard;
 
     use super::*;
-    use crate::{test_helper, ReplaceAll};
+    use crate::{
+        test_helper, AppendPrefix, Identity, LimitedBody, PathRewriter, RenameQuery, ReplaceAll,
+        SetQuery,
+    };
 
     async fn make_svc() -> (
         ServerGuard,
-        ReusedService<ReplaceAll<'static>, HttpConnector, String>,
+        ReusedService<ReplaceAll<&'static str>, HttpConnector, String>,
     ) {
         let server = mockito::Server::new_async().await;
         let uri = Uri::try_from(&server.url());
@@ -441,4 +592,273 @@ mod test {
         let (mut server, mut svc) = make_svc().await;
         test_helper::match_header(&mut server, &mut svc).await;
     }
+
+    #[tokio::test]
+    async fn upstream_version() {
+        let (mut server, svc) = make_svc().await;
+        let _mk = server
+            .mock("GET", "/goo")
+            .with_body("ok")
+            .create_async()
+            .await;
+
+        let request = || {
+            Request::builder()
+                .uri("https://test.com/foo")
+                .version(Version::HTTP_2)
+                .body(String::new())
+                .unwrap()
+        };
+
+        let result = svc.clone().call(request()).await.unwrap();
+        assert!(result.is_err());
+
+        let result = svc
+            .upstream_version(Version::HTTP_11)
+            .call(request())
+            .await
+            .unwrap();
+        assert_eq!(result.unwrap().version(), Version::HTTP_11);
+    }
+
+    #[tokio::test]
+    async fn rewrite_query() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("GET", "/goo")
+            .match_query("query=a+b&key=secret")
+            .with_body("ok")
+            .create_async()
+            .await;
+
+        let mut svc = builder_http(server.host_with_port()).unwrap().build(
+            ReplaceAll("foo", "goo")
+                .with_query(RenameQuery("q", "query"))
+                .with_query(SetQuery("key", "secret")),
+        );
+        let request = Request::get("http://test.com/foo?q=a%20b")
+            .body(String::new())
+            .unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn request_rewriter() {
+        struct ByMethod;
+
+        impl RequestRewriter for ByMethod {
+            fn rewrite_request<B>(
+                &mut self,
+                request: &mut Request<B>,
+                scheme: &Scheme,
+                authority: &Authority,
+            ) -> Result<(), Error> {
+                let prefix = format!("/{}", request.method().as_str().to_lowercase());
+                request
+                    .headers_mut()
+                    .insert("x-rewritten", http::HeaderValue::from_static("1"));
+                AppendPrefix(&prefix).rewrite_request(request, scheme, authority)
+            }
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("DELETE", "/delete/foo")
+            .match_header("x-rewritten", "1")
+            .with_body("ok")
+            .create_async()
+            .await;
+
+        let mut svc = builder_http(server.host_with_port())
+            .unwrap()
+            .build(ByMethod);
+        let request = Request::delete("http://test.com/foo")
+            .body(String::new())
+            .unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn host_map() {
+        use http_body_util::BodyExt;
+
+        use crate::rewrite::{HostMap, TrimPrefix, WithTarget};
+
+        let mut default = mockito::Server::new_async().await;
+        let mut eu = mockito::Server::new_async().await;
+        let _default = default
+            .mock("GET", "/asia")
+            .with_body("default")
+            .create_async()
+            .await;
+        let _eu = eu
+            .mock("GET", "/users")
+            .with_body("eu")
+            .create_async()
+            .await;
+
+        let hosts = HostMap::new().prefix(
+            "/eu",
+            Scheme::HTTP,
+            Authority::try_from(eu.host_with_port()).unwrap(),
+        );
+        let mut svc = builder_http(default.host_with_port())
+            .unwrap()
+            .build(WithTarget(hosts, TrimPrefix("/eu")));
+        for (uri, expected) in [("/eu/users", "eu"), ("/asia", "default")] {
+            let request = Request::get(uri).body(String::new()).unwrap();
+            let response = svc.call(request).await.unwrap().unwrap();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn rewrite_headers() {
+        use http::header::{HeaderName, HeaderValue};
+        use http::Method;
+
+        use crate::rewrite::HeaderRewriter;
+
+        let mut server = mockito::Server::new_async().await;
+        let mk = server
+            .mock("POST", "/users/42")
+            .match_header("x-http-method-override", "PUT")
+            .match_header("x-api-version", "2")
+            .with_header("server", "legacy")
+            .create_async()
+            .await;
+
+        let mut svc = builder_http(server.host_with_port())
+            .unwrap()
+            .request_headers(HeaderRewriter::new().override_method([Method::PUT]).set(
+                HeaderName::from_static("x-api-version"),
+                HeaderValue::from_static("2"),
+            ))
+            .response_headers(HeaderRewriter::new().rename(
+                HeaderName::from_static("server"),
+                HeaderName::from_static("x-upstream"),
+            ))
+            .build(Identity);
+        let request = Request::put("/users/42").body(String::new()).unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        mk.assert_async().await;
+        assert_eq!(response.headers()["x-upstream"], "legacy");
+        assert!(!response.headers().contains_key("server"));
+    }
+
+    #[tokio::test]
+    async fn max_body_size() {
+        use http_body_util::combinators::BoxBody;
+        use http_body_util::{BodyExt, Full};
+        use hyper::body::Bytes;
+
+        let mut server = mockito::Server::new_async().await;
+        let mk = server
+            .mock("POST", "/upload")
+            .match_body("abcd")
+            .create_async()
+            .await;
+
+        let mut svc =
+            builder_http::<LimitedBody<BoxBody<Bytes, Infallible>>, _>(server.host_with_port())
+                .unwrap()
+                .max_body_size(4)
+                .build(Identity);
+        let mut call = |body: &'static str, chunked: bool, length: Option<&str>| {
+            let full = Full::new(Bytes::from_static(body.as_bytes()));
+            // Without the size hint, the body is sent in chunks.
+            let body = if chunked {
+                full.map_frame(|frame| frame).boxed()
+            } else {
+                full.boxed()
+            };
+            let mut request = Request::post("http://test.com/upload");
+            if let Some(length) = length {
+                request = request.header("content-length", length);
+            }
+            svc.call(request.body(LimitedBody::new(body, 4)).unwrap())
+        };
+
+        for (body, chunked, length) in [
+            ("abcde", true, Some("5")),
+            ("abcde", false, None),
+            ("abcde", true, None),
+        ] {
+            let result = call(body, chunked, length).await.unwrap();
+            assert!(matches!(result, Err(Error::PayloadTooLarge)), "{body}");
+        }
+        let response = call("abcd", true, None).await.unwrap().unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        mk.assert_async().await;
+    }
+
+    #[cfg(feature = "http2")]
+    #[tokio::test]
+    async fn h2c() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("GET", "/goo")
+            .with_body("ok")
+            .create_async()
+            .await;
+
+        let mut svc = builder_h2c(server.host_with_port())
+            .unwrap()
+            .build(ReplaceAll("foo", "goo"));
+        let request = Request::builder()
+            .uri("https://test.com/foo")
+            .body(String::new())
+            .unwrap();
+        let result = svc.call(request).await.unwrap();
+        assert_eq!(result.unwrap().version(), Version::HTTP_2);
+    }
+
+    #[cfg(feature = "http2")]
+    #[tokio::test]
+    async fn grpc() {
+        let mut server = mockito::Server::new_async().await;
+        let _mk = server
+            .mock("POST", "/goo")
+            .match_header("te", "trailers")
+            .with_header("content-type", "application/grpc")
+            .create_async()
+            .await;
+
+        let mut svc = builder_h2c(server.host_with_port())
+            .unwrap()
+            .grpc()
+            .build(ReplaceAll("foo", "goo"));
+        let request = Request::post("https://test.com/foo")
+            .version(Version::HTTP_11)
+            .body(String::new())
+            .unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.version(), Version::HTTP_2);
+    }
+
+    #[cfg(feature = "http2")]
+    #[tokio::test]
+    async fn grpc_timeout() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = listener.local_addr().unwrap().to_string();
+        let _server = tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let mut svc = builder_h2c(authority)
+            .unwrap()
+            .grpc()
+            .build(ReplaceAll("foo", "goo"));
+        let request = Request::post("https://test.com/foo")
+            .header("grpc-timeout", "10m")
+            .body(String::new())
+            .unwrap();
+        let result = svc.call(request).await.unwrap();
+        assert!(matches!(result, Err(Error::Grpc(e)) if matches!(*e, Error::Timeout)));
+    }
 }
@@ -1,19 +1,95 @@
 //! A [`PathRewriter`] instance defines a rule to rewrite the request path.
 //!
-//! A "path" does not include a query. See [`http::uri::Uri`].
+//! A "path" does not include a query. See [`http::uri::Uri`]. The query is rewritten by a
+//! [`QueryRewriter`], combined with a [`PathRewriter`] by [`WithQuery`].
+//!
+//! Rewriters are combined by [`Chain`] and tuples, which apply them in order, and by [`Cond`] and
+//! [`FirstMatch`], which choose one by the path. A [`Template`] reuses the captures of an axum
+//! route.
+//!
+//! The rewriters with strings take any `AsRef<str>`, so they can be built from a runtime
+//! configuration. A `Box<dyn PathRewriter + Send>` or, to be cloned cheaply with the service, an
+//! `Arc<dyn SharedRewriter + Send + Sync>` holds a rewriter chosen at runtime.
+//!
+//! A [`RequestRewriter`] rewrites the whole request, seeing its method, headers and extensions.
+//! Every [`PathRewriter`] is one. [`Normalize`] removes the dot segments and duplicate slashes of
+//! the path before rewriting it, and [`Confine`] rejects the rewritten paths outside a prefix.
+//!
+//! A [`UriRewriter`] chooses the upstream of each request, such as a [`HostMap`] by the path,
+//! combined with a [`RequestRewriter`] by [`WithTarget`].
+//!
+//! The headers and the method of the requests and the responses are rewritten by a
+//! [`HeaderRewriter`], set on the services.
+//!
+//! With the `serde` feature, [`RewriteRules`] are read from a configuration file.
+//!
+//! The rewriters see the path as percent-encoded in the request, unless wrapped by [`Decoded`].
 
 use std::borrow::Cow;
+use std::sync::Arc;
 
 use http::uri::{Authority, Scheme, Uri};
 use http::{Error as HttpError, Request};
 use regex::{Regex as LibRegex, Replacer};
 
+use crate::Error;
+
+mod combinator;
+pub use combinator::*;
+mod decoded;
+pub use decoded::*;
+#[expect(clippy::module_name_repetitions)]
+mod header;
+pub use header::*;
+#[expect(clippy::module_name_repetitions)]
+mod host;
+pub use host::*;
+mod normalize;
+pub use normalize::*;
+#[expect(clippy::module_name_repetitions)]
+mod query;
+pub use query::*;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+#[expect(clippy::module_name_repetitions)]
+mod rule;
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+pub use rule::*;
+#[expect(clippy::module_name_repetitions)]
+mod template;
+pub use template::*;
+
 /// Represents a rule to rewrite a path `/foo/bar/baz` to new one.
 ///
 /// A "path" does not include a query. See [`http::uri::Uri`].
 pub trait PathRewriter {
     fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str>;
 
+    /// Rewrites the query, without the leading `?`. By default, the query is kept as is.
+    ///
+    /// A missing query is passed as an empty one. If the result is empty, the `?` is dropped,
+    /// unless the query is borrowed as is.
+    fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+        query.into()
+    }
+
+    /// Rewrites the query with `query` as well.
+    ///
+    /// ```
+    /// # use axum_proxy::rewrite::{PathRewriter, RemoveQuery, TrimPrefix};
+    /// let mut rw = TrimPrefix("/api").with_query(RemoveQuery("utm_source"));
+    /// assert_eq!(rw.rewrite("/api/foo"), "/foo");
+    /// assert_eq!(rw.rewrite_query("q=1&utm_source=mail"), "q=1");
+    /// ```
+    fn with_query<Q>(self, query: Q) -> WithQuery<Self, Q>
+    where
+        Self: Sized,
+        Q: QueryRewriter,
+    {
+        WithQuery(self, query)
+    }
+
     /// # Errors
     ///
     /// When the rewritten path is invalid
@@ -22,21 +98,22 @@ pub trait PathRewriter {
         request: &mut Request<B>,
         scheme: &Scheme,
         authority: &Authority,
-    ) -> Result<(), HttpError> {
+    ) -> Result<(), HttpError>
+    where
+        Self: Sized,
+    {
         let original_uri = request.uri();
-        let path = self.rewrite(original_uri.path());
-
-        let rewritten_path = {
-            if let Some(query) = original_uri.query() {
-                let mut p_and_q = path.into_owned();
-                p_and_q.push('?');
-                p_and_q.push_str(query);
-
-                p_and_q
-            } else {
-                path.into()
-            }
-        };
+        let mut rewritten_path = self.rewrite(original_uri.path()).into_owned();
+
+        let query = original_uri.query();
+        let rewritten_query = self.rewrite_query(query.unwrap_or_default());
+        // An empty query is kept only if it is left as is.
+        if !rewritten_query.is_empty()
+            || (query.is_some() && matches!(rewritten_query, Cow::Borrowed(_)))
+        {
+            rewritten_path.push('?');
+            rewritten_path.push_str(&rewritten_query);
+        }
 
         let rewritten_uri = Uri::builder()
             .scheme(scheme.clone())
@@ -50,6 +127,149 @@ pub trait PathRewriter {
     }
 }
 
+/// Represents a rule to rewrite a whole request, such as its path, headers and target.
+///
+/// Unlike [`PathRewriter`], this sees the method, headers and extensions of the request. Every
+/// [`PathRewriter`] is a `RequestRewriter`, so the services accept either.
+///
+/// ```
+/// # use axum_proxy::rewrite::{AppendPrefix, RequestRewriter};
+/// # use axum_proxy::Error;
+/// # use http::uri::{Authority, Scheme};
+/// # use http::Request;
+/// /// Routes to `/{tenant}/...` by the `x-tenant` header.
+/// struct Tenant;
+///
+/// impl RequestRewriter for Tenant {
+///     fn rewrite_request<B>(
+///         &mut self,
+///         request: &mut Request<B>,
+///         scheme: &Scheme,
+///         authority: &Authority,
+///     ) -> Result<(), Error> {
+///         let tenant = match request.headers_mut().remove("x-tenant") {
+///             Some(value) => value.to_str().unwrap_or("default").to_owned(),
+///             None => "default".to_owned(),
+///         };
+///         AppendPrefix(&format!("/{tenant}")).rewrite_request(request, scheme, authority)
+///     }
+/// }
+///
+/// let mut request = Request::get("/users").header("x-tenant", "acme").body(()).unwrap();
+/// Tenant
+///     .rewrite_request(&mut request, &Scheme::HTTP, &Authority::from_static("backend"))
+///     .unwrap();
+/// assert_eq!(request.uri(), "http://backend/acme/users");
+/// assert!(!request.headers().contains_key("x-tenant"));
+/// ```
+pub trait RequestRewriter {
+    /// Rewrites `request`, whose URI must be set to the absolute one toward the upstream at
+    /// `scheme` and `authority`.
+    ///
+    /// # Errors
+    ///
+    /// When the request cannot be rewritten, which is returned from the service as is.
+    fn rewrite_request<B>(
+        &mut self,
+        request: &mut Request<B>,
+        scheme: &Scheme,
+        authority: &Authority,
+    ) -> Result<(), Error>;
+}
+
+impl<P: PathRewriter> RequestRewriter for P {
+    fn rewrite_request<B>(
+        &mut self,
+        request: &mut Request<B>,
+        scheme: &Scheme,
+        authority: &Authority,
+    ) -> Result<(), Error> {
+        self.rewrite_uri(request, scheme, authority)
+            .map_err(Error::InvalidUri)
+    }
+}
+
+/// Represents a rule to choose the upstream of a request, instead of the one of the service.
+///
+/// This is combined with a [`RequestRewriter`] by [`WithTarget`]. See [`HostMap`].
+pub trait UriRewriter {
+    /// Returns the scheme and authority of the upstream to send `request` to, or `None` to send it
+    /// to the one of the service.
+    fn rewrite_target<B>(&mut self, request: &Request<B>) -> Option<(Scheme, Authority)>;
+}
+
+/// Represents a rule to rewrite a path without mutable state, shared by [`Arc`].
+///
+/// Services are cloned for every request by axum and others, so a rewriter built at runtime is
+/// best held by an `Arc<dyn SharedRewriter + Send + Sync>`, which implements [`PathRewriter`] and
+/// is cloned cheaply. The built-in rewriters implement this, except [`Func`], [`Cond`] and the
+/// query rewriters, which take `&mut self`.
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use axum_proxy::rewrite::{AppendPrefix, PathRewriter, SharedRewriter, TrimPrefix};
+/// let prefix = String::from("/api");
+/// let mut rw: Arc<dyn SharedRewriter + Send + Sync> = if prefix.is_empty() {
+///     Arc::new(AppendPrefix("/v2"))
+/// } else {
+///     Arc::new(TrimPrefix(prefix))
+/// };
+/// assert_eq!(rw.rewrite("/api/users"), "/users");
+/// ```
+pub trait SharedRewriter {
+    fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str>;
+
+    /// Same as [`PathRewriter::rewrite_query()`]. By default, the query is kept as is.
+    fn rewrite_query_shared<'a>(&'a self, query: &'a str) -> Cow<'a, str> {
+        query.into()
+    }
+}
+
+impl<P: SharedRewriter + ?Sized> PathRewriter for Arc<P> {
+    fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+        (**self).rewrite_shared(path)
+    }
+
+    fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+        (**self).rewrite_query_shared(query)
+    }
+}
+
+/// This makes `Box<dyn PathRewriter + Send>` a rewriter, for the ones chosen at runtime.
+impl<P: PathRewriter + ?Sized> PathRewriter for Box<P> {
+    fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+        (**self).rewrite(path)
+    }
+
+    fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+        (**self).rewrite_query(query)
+    }
+}
+
+/// Implements [`PathRewriter`] for types implementing [`SharedRewriter`].
+macro_rules! impl_path_rewriter {
+    (@impl [$($generics:tt)*] $ty:ty) => {
+        impl<$($generics)*> PathRewriter for $ty {
+            #[inline]
+            fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+                self.rewrite_shared(path)
+            }
+
+            #[inline]
+            fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+                self.rewrite_query_shared(query)
+            }
+        }
+    };
+    (<$($param:ident: $bound:path),+> $ty:ty) => {
+        impl_path_rewriter!(@impl [$($param: $bound),+] $ty);
+    };
+    ($ty:ty) => {
+        impl_path_rewriter!(@impl [] $ty);
+    };
+}
+pub(crate) use impl_path_rewriter;
+
 /// Identity function, that is, this returns the `path` as is.
 ///
 /// ```
@@ -59,68 +279,87 @@ pub trait PathRewriter {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Identity;
 
-impl PathRewriter for Identity {
+impl SharedRewriter for Identity {
     #[inline]
-    fn rewrite<'a>(&mut self, path: &'a str) -> Cow<'a, str> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
         path.into()
     }
 }
 
+impl_path_rewriter!(Identity);
+
 /// Returns `self.0` regardless what the `path` is.
 ///
+/// Like the other rewriters with strings, this takes any `AsRef<str>` such as `&str`, `String`
+/// or `Arc<str>`, so it can be built from a runtime configuration.
+///
 /// ```
 /// # use axum_proxy::rewrite::{PathRewriter, Static};
 /// assert_eq!(Static("bar").rewrite("foo"), "bar");
+/// assert_eq!(Static(String::from("bar")).rewrite("foo"), "bar");
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Static<'a>(pub &'a str);
+pub struct Static<S>(pub S);
 
-impl PathRewriter for Static<'_> {
+impl<S: AsRef<str>> SharedRewriter for Static<S> {
     #[inline]
-    fn rewrite<'a>(&'a mut self, _path: &'a str) -> Cow<'a, str> {
-        self.0.into()
+    fn rewrite_shared<'a>(&'a self, _path: &'a str) -> Cow<'a, str> {
+        self.0.as_ref().into()
     }
 }
 
+impl_path_rewriter!(<S: AsRef<str>> Static<S>);
+
 /// `ReplaceAll(old, new)` replaces all matches `old` with `new`.
 ///
+/// `old` and `new` may be of different types.
+///
 /// ```
 /// # use axum_proxy::rewrite::{PathRewriter, ReplaceAll};
 /// assert_eq!(ReplaceAll("foo", "bar").rewrite("foofoo"), "barbar");
+/// assert_eq!(ReplaceAll("foo", String::from("bar")).rewrite("foofoo"), "barbar");
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct ReplaceAll<'a>(pub &'a str, pub &'a str);
+pub struct ReplaceAll<S, R = S>(pub S, pub R);
 
-impl PathRewriter for ReplaceAll<'_> {
-    fn rewrite<'a>(&mut self, path: &'a str) -> Cow<'a, str> {
-        if path.contains(self.0) {
-            path.replace(self.0, self.1).into()
+impl<S: AsRef<str>, R: AsRef<str>> SharedRewriter for ReplaceAll<S, R> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let (old, new) = (self.0.as_ref(), self.1.as_ref());
+        if path.contains(old) {
+            path.replace(old, new).into()
         } else {
             path.into()
         }
     }
 }
 
+impl_path_rewriter!(<S: AsRef<str>, R: AsRef<str>> ReplaceAll<S, R>);
+
 /// `ReplaceN(old, new, n)` replaces first `n` matches `old` with `new`.
 ///
+/// As for [`ReplaceAll`], `old` and `new` may be of different types.
+///
 /// ```
 /// # use axum_proxy::rewrite::{PathRewriter, ReplaceN};
 /// assert_eq!(ReplaceN("foo", "bar", 1).rewrite("foofoo"), "barfoo");
 /// assert_eq!(ReplaceN("foo", "bar", 3).rewrite("foofoo"), "barbar");
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct ReplaceN<'a>(pub &'a str, pub &'a str, pub usize);
+pub struct ReplaceN<S, R = S>(pub S, pub R, pub usize);
 
-impl PathRewriter for ReplaceN<'_> {
-    fn rewrite<'a>(&mut self, path: &'a str) -> Cow<'a, str> {
-        if path.contains(self.0) {
-            path.replacen(self.0, self.1, self.2).into()
+impl<S: AsRef<str>, R: AsRef<str>> SharedRewriter for ReplaceN<S, R> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let (old, new) = (self.0.as_ref(), self.1.as_ref());
+        if path.contains(old) {
+            path.replacen(old, new, self.2).into()
         } else {
             path.into()
         }
     }
 }
 
+impl_path_rewriter!(<S: AsRef<str>, R: AsRef<str>> ReplaceN<S, R>);
+
 /// Trims a prefix if exists.
 ///
 /// ```
@@ -129,11 +368,11 @@ impl PathRewriter for ReplaceN<'_> {
 /// assert_eq!(TrimPrefix("bar").rewrite("foobarfoo"), "foobarfoo");
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct TrimPrefix<'a>(pub &'a str);
+pub struct TrimPrefix<S>(pub S);
 
-impl PathRewriter for TrimPrefix<'_> {
-    fn rewrite<'a>(&mut self, path: &'a str) -> Cow<'a, str> {
-        if let Some(stripped) = path.strip_prefix(self.0) {
+impl<S: AsRef<str>> SharedRewriter for TrimPrefix<S> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        if let Some(stripped) = path.strip_prefix(self.0.as_ref()) {
             stripped.into()
         } else {
             path.into()
@@ -141,6 +380,8 @@ impl PathRewriter for TrimPrefix<'_> {
     }
 }
 
+impl_path_rewriter!(<S: AsRef<str>> TrimPrefix<S>);
+
 /// Trims a suffix if exists.
 ///
 /// ```
@@ -149,11 +390,11 @@ impl PathRewriter for TrimPrefix<'_> {
 /// assert_eq!(TrimSuffix("bar").rewrite("foobarfoo"), "foobarfoo");
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct TrimSuffix<'a>(pub &'a str);
+pub struct TrimSuffix<S>(pub S);
 
-impl PathRewriter for TrimSuffix<'_> {
-    fn rewrite<'a>(&mut self, path: &'a str) -> Cow<'a, str> {
-        if let Some(stripped) = path.strip_suffix(self.0) {
+impl<S: AsRef<str>> SharedRewriter for TrimSuffix<S> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        if let Some(stripped) = path.strip_suffix(self.0.as_ref()) {
             stripped.into()
         } else {
             path.into()
@@ -161,6 +402,8 @@ impl PathRewriter for TrimSuffix<'_> {
     }
 }
 
+impl_path_rewriter!(<S: AsRef<str>> TrimSuffix<S>);
+
 /// Appends a prefix.
 ///
 /// ```
@@ -168,17 +411,20 @@ impl PathRewriter for TrimSuffix<'_> {
 /// assert_eq!(AppendPrefix("foo").rewrite("bar"), "foobar");
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct AppendPrefix<'a>(pub &'a str);
+pub struct AppendPrefix<S>(pub S);
 
-impl PathRewriter for AppendPrefix<'_> {
-    fn rewrite<'a>(&mut self, path: &'a str) -> Cow<'a, str> {
-        let mut ret = String::with_capacity(self.0.len() + path.len());
-        ret.push_str(self.0);
+impl<S: AsRef<str>> SharedRewriter for AppendPrefix<S> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let prefix = self.0.as_ref();
+        let mut ret = String::with_capacity(prefix.len() + path.len());
+        ret.push_str(prefix);
         ret.push_str(path);
         ret.into()
     }
 }
 
+impl_path_rewriter!(<S: AsRef<str>> AppendPrefix<S>);
+
 /// Appends a suffix.
 ///
 /// ```
@@ -186,21 +432,25 @@ impl PathRewriter for AppendPrefix<'_> {
 /// assert_eq!(AppendSuffix("foo").rewrite("bar"), "barfoo");
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct AppendSuffix<'a>(pub &'a str);
+pub struct AppendSuffix<S>(pub S);
 
-impl PathRewriter for AppendSuffix<'_> {
-    fn rewrite<'a>(&mut self, path: &'a str) -> Cow<'a, str> {
-        let mut ret = String::with_capacity(self.0.len() + path.len());
+impl<S: AsRef<str>> SharedRewriter for AppendSuffix<S> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let suffix = self.0.as_ref();
+        let mut ret = String::with_capacity(suffix.len() + path.len());
         ret.push_str(path);
-        ret.push_str(self.0);
+        ret.push_str(suffix);
         ret.into()
     }
 }
 
+impl_path_rewriter!(<S: AsRef<str>> AppendSuffix<S>);
+
 /// `RegexAll(re, new)` replaces all matches `re` with `new`.
 ///
 /// The type of `new` must implement [`Replacer`].
-/// See [`regex`] for details.
+/// See [`regex`] for details. As a [`SharedRewriter`], `new` is cloned on every rewrite, which is
+/// cheap for `&str`.
 ///
 /// ```
 /// # use axum_proxy::rewrite::{PathRewriter, RegexAll};
@@ -220,10 +470,17 @@ impl<Rep: Replacer> PathRewriter for RegexAll<Rep> {
     }
 }
 
+impl<Rep: Replacer + Clone> SharedRewriter for RegexAll<Rep> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        self.0.replace_all(path, self.1.clone())
+    }
+}
+
 /// `RegexN(re, new, n)` replaces first `n` matches `re` with `new`.
 ///
 /// The type of `new` must implement [`Replacer`].
-/// See [`regex`] for details.
+/// See [`regex`] for details. As a [`SharedRewriter`], `new` is cloned on every rewrite, which is
+/// cheap for `&str`.
 ///
 /// ```
 /// # use axum_proxy::rewrite::{PathRewriter, RegexN};
@@ -247,6 +504,12 @@ impl<Rep: Replacer> PathRewriter for RegexN<Rep> {
     }
 }
 
+impl<Rep: Replacer + Clone> SharedRewriter for RegexN<Rep> {
+    fn rewrite_shared<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        self.0.replacen(path, self.2, self.1.clone())
+    }
+}
+
 /// Converts the `path` by a function.
 ///
 /// The type of the function must be `for<'a> FnMut(&'a str) -> String`.
@@ -341,6 +604,33 @@ mod test {
         assert_eq!(rw.rewrite(path), "/10-21-2021/12-02-2021/2022/01/13");
     }
 
+    #[test]
+    fn owned() {
+        let path = "/foo/bar";
+        let prefix: Arc<str> = "/foo".into();
+        let mut rw = Chain(TrimPrefix(prefix), AppendSuffix(String::from("/baz")));
+        assert_eq!(rw.rewrite(path), "/bar/baz");
+
+        let mut rw: Box<dyn PathRewriter + Send> = Box::new(Func(|path: &str| path.repeat(2)));
+        assert_eq!(rw.rewrite(path), "/foo/bar/foo/bar");
+
+        let shared: Arc<dyn SharedRewriter + Send + Sync> = Arc::new(Chain(
+            ReplaceAll(String::from("bar"), String::from("baz")),
+            Identity,
+        ));
+        let mut rw = shared.clone();
+        assert_eq!(rw.rewrite(path), "/foo/baz");
+        assert!(matches!(rw.rewrite("/qux"), Cow::Borrowed("/qux")));
+        assert_eq!(Arc::strong_count(&shared), 2);
+
+        let mut rw: Arc<dyn SharedRewriter + Send + Sync> = Arc::new((
+            FirstMatch(vec![(LibRegex::new("^/foo").unwrap(), "/qux")]),
+            ReplaceAll("bar", String::from("baz")),
+            Identity,
+        ));
+        assert_eq!(rw.rewrite(path), "/qux/baz");
+    }
+
     #[test]
     fn func() {
         let path = "/abcdefg";
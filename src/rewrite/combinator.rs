@@ -0,0 +1,284 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use regex::{Regex as LibRegex, Replacer};
+
+use super::{PathRewriter, SharedRewriter};
+
+/// Witnesses `'a: 'b` for the closure of [`then()`], so that it may reborrow a rewriter living
+/// for `'a` as long as its input.
+pub(super) type Outlives<'b, 'a> = PhantomData<&'b &'a ()>;
+
+/// Rewrites `s`, a path or a query which may be borrowed from an earlier rewriter, with
+/// `rewrite`.
+///
+/// An owned `s` is not copied if `rewrite` leaves it as is.
+pub(super) fn then<'a>(
+    s: Cow<'a, str>,
+    rewrite: impl for<'b> FnOnce(&'b str, Outlives<'b, 'a>) -> Cow<'b, str>,
+) -> Cow<'a, str> {
+    match s {
+        Cow::Borrowed(s) => rewrite(s, PhantomData),
+        Cow::Owned(s) => {
+            let rewritten = match rewrite(&s, PhantomData) {
+                Cow::Borrowed(rewritten) if std::ptr::eq(rewritten, s.as_str()) => None,
+                rewritten => Some(rewritten.into_owned()),
+            };
+            rewritten.unwrap_or(s).into()
+        },
+    }
+}
+
+/// `Chain(a, b)` rewrites with `a`, and then with `b`.
+///
+/// Tuples such as `(a, b, c)` do the same for more rewriters. The path is borrowed as long as
+/// none of them changes it.
+///
+/// ```
+/// # use axum_proxy::rewrite::{AppendPrefix, Chain, PathRewriter, TrimPrefix};
+/// let mut rw = Chain(TrimPrefix("/api"), AppendPrefix("/v2"));
+/// assert_eq!(rw.rewrite("/api/users"), "/v2/users");
+///
+/// let mut rw = (TrimPrefix("/api"), AppendPrefix("/v2"), TrimPrefix("/v2/v2"));
+/// assert_eq!(rw.rewrite("/api/v2/users"), "/users");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chain<A, B>(pub A, pub B);
+
+impl<A: PathRewriter, B: PathRewriter> PathRewriter for Chain<A, B> {
+    fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+        then(self.0.rewrite(path), |path, _| self.1.rewrite(path))
+    }
+
+    fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+        then(self.0.rewrite_query(query), |query, _| {
+            self.1.rewrite_query(query)
+        })
+    }
+}
+
+impl<A: SharedRewriter, B: SharedRewriter> SharedRewriter for Chain<A, B> {
+    fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str> {
+        then(self.0.rewrite_shared(path), |path, _| {
+            self.1.rewrite_shared(path)
+        })
+    }
+
+    fn rewrite_query_shared<'a>(&'a self, query: &'a str) -> Cow<'a, str> {
+        then(self.0.rewrite_query_shared(query), |query, _| {
+            self.1.rewrite_query_shared(query)
+        })
+    }
+}
+
+macro_rules! impl_tuple {
+    ($first:ident $(, $rest:ident)*) => {
+        impl<$first: PathRewriter, $($rest: PathRewriter),*> PathRewriter for ($first, $($rest),*) {
+            #[allow(non_snake_case)]
+            fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+                let ($first, $($rest),*) = self;
+                let path = $first.rewrite(path);
+                $(let path = then(path, move |path, _| $rest.rewrite(path));)*
+                path
+            }
+
+            #[allow(non_snake_case)]
+            fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+                let ($first, $($rest),*) = self;
+                let query = $first.rewrite_query(query);
+                $(let query = then(query, move |query, _| $rest.rewrite_query(query));)*
+                query
+            }
+        }
+
+        impl<$first: SharedRewriter, $($rest: SharedRewriter),*> SharedRewriter
+            for ($first, $($rest),*)
+        {
+            #[allow(non_snake_case)]
+            fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str> {
+                let ($first, $($rest),*) = self;
+                let path = $first.rewrite_shared(path);
+                $(let path = then(path, move |path, _| $rest.rewrite_shared(path));)*
+                path
+            }
+
+            #[allow(non_snake_case)]
+            fn rewrite_query_shared<'a>(&'a self, query: &'a str) -> Cow<'a, str> {
+                let ($first, $($rest),*) = self;
+                let query = $first.rewrite_query_shared(query);
+                $(let query = then(query, move |query, _| $rest.rewrite_query_shared(query));)*
+                query
+            }
+        }
+    };
+}
+
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+
+/// A condition on the path, for [`Cond`].
+///
+/// This is implemented for [`Regex`](regex::Regex) and `FnMut(&str) -> bool`.
+pub trait PathMatcher {
+    fn matches(&mut self, path: &str) -> bool;
+}
+
+impl PathMatcher for LibRegex {
+    fn matches(&mut self, path: &str) -> bool {
+        self.is_match(path)
+    }
+}
+
+impl<F> PathMatcher for F
+where
+    F: FnMut(&str) -> bool,
+{
+    fn matches(&mut self, path: &str) -> bool {
+        self(path)
+    }
+}
+
+/// `Cond::new(matcher, a, b)` rewrites with `a` if the path matches `matcher`, or with `b`
+/// otherwise.
+///
+/// The query is rewritten by the branch chosen for the path, so the path must be rewritten
+/// first, as [`PathRewriter::rewrite_uri()`] does. Until then, `b` is chosen.
+///
+/// ```
+/// # use axum_proxy::rewrite::{AppendPrefix, Cond, Identity, PathRewriter, RemoveQuery};
+/// let mut rw = Cond::new(
+///     |path: &str| !path.starts_with("/static/"),
+///     AppendPrefix("/app").with_query(RemoveQuery("v")),
+///     Identity,
+/// );
+/// assert_eq!(rw.rewrite("/users"), "/app/users");
+/// assert_eq!(rw.rewrite_query("v=1&a=2"), "a=2");
+/// assert_eq!(rw.rewrite("/static/logo.png"), "/static/logo.png");
+/// assert_eq!(rw.rewrite_query("v=1&a=2"), "v=1&a=2");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cond<M, A, B> {
+    matcher: M,
+    then: A,
+    otherwise: B,
+    /// Whether the last path matched.
+    matched: bool,
+}
+
+impl<M, A, B> Cond<M, A, B> {
+    pub fn new(matcher: M, then: A, otherwise: B) -> Self {
+        Self {
+            matcher,
+            then,
+            otherwise,
+            matched: false,
+        }
+    }
+}
+
+impl<M: PathMatcher, A: PathRewriter, B: PathRewriter> PathRewriter for Cond<M, A, B> {
+    fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+        self.matched = self.matcher.matches(path);
+        if self.matched {
+            self.then.rewrite(path)
+        } else {
+            self.otherwise.rewrite(path)
+        }
+    }
+
+    fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+        if self.matched {
+            self.then.rewrite_query(query)
+        } else {
+            self.otherwise.rewrite_query(query)
+        }
+    }
+}
+
+/// `FirstMatch(rules)` replaces all matches of the first regex matching the path in `rules` with
+/// its replacement, as [`RegexAll`](super::RegexAll) does.
+///
+/// If no regex matches, the path is kept as is. As a [`SharedRewriter`], the replacement is cloned
+/// on every rewrite, as for [`RegexAll`](super::RegexAll).
+///
+/// ```
+/// # use axum_proxy::rewrite::{FirstMatch, PathRewriter};
+/// # use regex::Regex;
+/// let mut rw = FirstMatch(vec![
+///     (Regex::new(r"^/users/(\d+)$").unwrap(), "/accounts/$1"),
+///     (Regex::new(r"^/users").unwrap(), "/people"),
+/// ]);
+/// assert_eq!(rw.rewrite("/users/42"), "/accounts/42");
+/// assert_eq!(rw.rewrite("/users/me"), "/people/me");
+/// assert_eq!(rw.rewrite("/posts"), "/posts");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FirstMatch<Rep>(pub Vec<(LibRegex, Rep)>);
+
+impl<Rep: Replacer> PathRewriter for FirstMatch<Rep> {
+    fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+        match self.0.iter_mut().find(|(re, _)| re.is_match(path)) {
+            Some((re, rep)) => re.replace_all(path, rep.by_ref()),
+            None => path.into(),
+        }
+    }
+}
+
+impl<Rep: Replacer + Clone> SharedRewriter for FirstMatch<Rep> {
+    fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str> {
+        match self.0.iter().find(|(re, _)| re.is_match(path)) {
+            Some((re, rep)) => re.replace_all(path, rep.clone()),
+            None => path.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rewrite::{AppendSuffix, Identity, RemoveQuery, ReplaceAll, TrimPrefix};
+
+    #[test]
+    fn borrow() {
+        let path = "/foo/bar";
+        let mut rw = (TrimPrefix("/foo"), Identity, TrimPrefix("/baz"));
+        assert!(matches!(rw.rewrite(path), Cow::Borrowed("/bar")));
+
+        let mut rw = Chain(Identity, ReplaceAll("baz", "qux"));
+        assert!(matches!(rw.rewrite(path), Cow::Borrowed(p) if std::ptr::eq(p, path)));
+
+        let mut rw = Chain(AppendSuffix("/"), TrimPrefix("/baz"));
+        assert!(matches!(rw.rewrite(path), Cow::Owned(p) if p == "/foo/bar/"));
+
+        let mut rw = FirstMatch(vec![(LibRegex::new("^/baz").unwrap(), "")]);
+        assert!(matches!(rw.rewrite(path), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn query() {
+        let mut rw = (
+            TrimPrefix("/api").with_query(RemoveQuery("a")),
+            Identity,
+            Identity.with_query(RemoveQuery("b")),
+        );
+        assert_eq!(rw.rewrite("/api/foo"), "/foo");
+        assert_eq!(rw.rewrite_query("a=1&b=2&c=3"), "c=3");
+        assert!(matches!(rw.rewrite_query("c=3"), Cow::Borrowed("c=3")));
+
+        let mut rw = Cond::new(
+            LibRegex::new("^/api").unwrap(),
+            Identity.with_query(RemoveQuery("a")),
+            Identity.with_query(RemoveQuery("b")),
+        );
+        assert_eq!(rw.rewrite_query("a=1&b=2"), "a=1");
+        assert_eq!(rw.rewrite("/api/foo"), "/api/foo");
+        assert_eq!(rw.rewrite_query("a=1&b=2"), "b=2");
+        assert_eq!(rw.rewrite("/foo"), "/foo");
+        assert_eq!(rw.rewrite_query("a=1&b=2"), "a=1");
+    }
+}
@@ -0,0 +1,151 @@
+use std::borrow::Cow;
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet};
+
+use super::template::SEGMENT;
+use super::{PathRewriter, SharedRewriter};
+
+/// The characters encoded in a path, that is, in its segments but `/` and `%`.
+const PATH: &AsciiSet = &SEGMENT.remove(b'/').remove(b'%');
+
+/// Decodes the segments of `path` but `%` and `/` in them, kept as `%25` and `%2F`.
+///
+/// Returns `None` if a segment is not UTF-8 once decoded.
+fn decode(path: &str) -> Option<String> {
+    let mut decoded = String::with_capacity(path.len());
+    for (i, segment) in path.split('/').enumerate() {
+        if i > 0 {
+            decoded.push('/');
+        }
+        for c in percent_decode_str(segment).decode_utf8().ok()?.chars() {
+            match c {
+                '%' => decoded.push_str("%25"),
+                '/' => decoded.push_str("%2F"),
+                c => decoded.push(c),
+            }
+        }
+    }
+    Some(decoded)
+}
+
+/// Returns the result of `rewritten` for the decoded `decoded` of `path`, re-encoded.
+///
+/// A `%` starts an escape if followed by two hexadecimal digits, and is encoded otherwise. `path`
+/// is returned as is if left untouched, even if it is not encoded canonically.
+fn encode<'a>(path: &'a str, decoded: &str, rewritten: &str) -> Cow<'a, str> {
+    if std::ptr::eq(rewritten, decoded) {
+        return path.into();
+    }
+    let mut encoded = String::with_capacity(rewritten.len());
+    for (i, part) in rewritten.split('%').enumerate() {
+        if i > 0 {
+            let escape = part
+                .as_bytes()
+                .get(..2)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+            encoded.push_str(if escape { "%" } else { "%25" });
+        }
+        encoded.extend(utf8_percent_encode(part, PATH));
+    }
+    encoded.into()
+}
+
+/// `Decoded(rewriter)` rewrites the path with percent-decoded segments with `rewriter`, and
+/// encodes the result again.
+///
+/// The built-in rewriters see the path as encoded in the request, so `ReplaceAll("café", ..)`
+/// does not match `/caf%C3%A9`, and a [`RegexAll`](super::RegexAll) may put characters not
+/// allowed in a URI, failing with [`Error::InvalidUri`](crate::Error::InvalidUri). With this,
+/// they see `/café` instead, and the result is encoded canonically.
+///
+/// The segments are decoded one by one, but `%` and `/` in them are kept as `%25` and `%2F`, so
+/// an encoded slash never separates segments nor becomes a dot segment such as `..%2F`. A `%`
+/// put by the rewriter is encoded unless it starts an escape. A path with a segment that is not
+/// UTF-8 once decoded is rewritten as is. The query is rewritten as is, since query rewriters
+/// already work on the decoded pairs.
+///
+/// ```
+/// # use axum_proxy::rewrite::{Decoded, PathRewriter, ReplaceAll, TrimPrefix};
+/// let mut rw = Decoded(ReplaceAll("café", "tea room"));
+/// assert_eq!(rw.rewrite("/caf%C3%A9/menu"), "/tea%20room/menu");
+/// assert_eq!(rw.rewrite("/bar/%7e"), "/bar/%7e");
+///
+/// let mut rw = Decoded(TrimPrefix("/public"));
+/// assert_eq!(rw.rewrite("/public/..%2Fadmin"), "/..%2Fadmin");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decoded<P>(pub P);
+
+impl<P: PathRewriter> PathRewriter for Decoded<P> {
+    fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+        let Some(decoded) = decode(path) else {
+            return self.0.rewrite(path);
+        };
+        encode(path, &decoded, &self.0.rewrite(&decoded))
+    }
+
+    fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+        self.0.rewrite_query(query)
+    }
+}
+
+impl<P: SharedRewriter> SharedRewriter for Decoded<P> {
+    fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str> {
+        let Some(decoded) = decode(path) else {
+            return self.0.rewrite_shared(path);
+        };
+        encode(path, &decoded, &self.0.rewrite_shared(&decoded))
+    }
+
+    fn rewrite_query_shared<'a>(&'a self, query: &'a str) -> Cow<'a, str> {
+        self.0.rewrite_query_shared(query)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::Request;
+    use regex::Regex;
+
+    use super::*;
+    use crate::rewrite::{Identity, RegexAll, ReplaceAll, TrimPrefix};
+
+    #[test]
+    fn rewrite() {
+        let path = "/caf%c3%a9/%7E";
+        assert!(matches!(
+            Decoded(Identity).rewrite(path),
+            Cow::Borrowed(p) if std::ptr::eq(p, path)
+        ));
+        assert_eq!(Decoded(TrimPrefix("/café")).rewrite(path), "/~");
+        assert_eq!(
+            Decoded(TrimPrefix("/a")).rewrite("/a/50%25/b%2fc"),
+            "/50%25/b%2Fc"
+        );
+        assert_eq!(
+            Decoded(TrimPrefix("/public")).rewrite("/public/..%2Fadmin"),
+            "/..%2Fadmin"
+        );
+        assert_eq!(
+            Decoded(ReplaceAll("c", "%2F%")).rewrite("/c%252F"),
+            "/%2F%25%252F"
+        );
+
+        // Not UTF-8
+        assert_eq!(Decoded(TrimPrefix("/a")).rewrite("/a/%FF"), "/%FF");
+    }
+
+    #[test]
+    fn rewrite_uri() {
+        let re = Regex::new("^/search/(.+)$").unwrap();
+        let mut rw = Decoded(RegexAll(re, "/q/$1 \"{x}\""));
+        let scheme = "http".try_into().unwrap();
+        let authority = "example.com".try_into().unwrap();
+        let mut request = Request::get("/search/%E6%97%A5%20?a=1").body(()).unwrap();
+        rw.rewrite_uri(&mut request, &scheme, &authority).unwrap();
+        assert_eq!(
+            request.uri(),
+            "http://example.com/q/%E6%97%A5%20%20%22%7Bx%7D%22?a=1"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::borrow::Cow;
+
+use http::header::{Entry, HeaderMap, HeaderName, HeaderValue};
+use http::{Method, Request, Response};
+use regex::Regex as LibRegex;
+
+/// The name of the header carrying the original method, set by
+/// [`HeaderRewriter::override_method()`].
+const METHOD_OVERRIDE: HeaderName = HeaderName::from_static("x-http-method-override");
+
+/// A rule of a [`HeaderRewriter`].
+#[derive(Debug, Clone)]
+enum Rule {
+    Set(HeaderName, HeaderValue),
+    Append(HeaderName, HeaderValue),
+    Remove(HeaderName),
+    Rename(HeaderName, HeaderName),
+    Replace(HeaderName, LibRegex, String),
+}
+
+/// Rewrites the headers, and the method, of requests or responses.
+///
+/// The rules are applied in the order they are added. The services apply one to the requests
+/// before sending them to the upstream, after the path is rewritten, and another one to the
+/// responses, set by e.g. [`ReusedService::request_headers()`](crate::ReusedService::request_headers)
+/// and [`ReusedService::response_headers()`](crate::ReusedService::response_headers).
+///
+/// ```
+/// # use axum_proxy::rewrite::HeaderRewriter;
+/// # use http::header::HeaderValue;
+/// # use http::{HeaderName, Method, Request};
+/// # use regex::Regex;
+/// let rw = HeaderRewriter::new()
+///     .override_method([Method::PUT, Method::PATCH])
+///     .set(HeaderName::from_static("x-api-version"), HeaderValue::from_static("2"))
+///     .remove(HeaderName::from_static("cookie"))
+///     .rename(
+///         HeaderName::from_static("x-token"),
+///         HeaderName::from_static("authorization"),
+///     )
+///     .replace(
+///         HeaderName::from_static("authorization"),
+///         Regex::new("^(.*)$").unwrap(),
+///         "Bearer $1",
+///     );
+///
+/// let mut request = Request::put("/users/42")
+///     .header("cookie", "session=1")
+///     .header("x-token", "secret")
+///     .body(())
+///     .unwrap();
+/// rw.rewrite_request(&mut request);
+/// assert_eq!(request.method(), Method::POST);
+/// assert_eq!(request.headers()["x-http-method-override"], "PUT");
+/// assert_eq!(request.headers()["x-api-version"], "2");
+/// assert_eq!(request.headers()["authorization"], "Bearer secret");
+/// assert!(!request.headers().contains_key("cookie"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HeaderRewriter {
+    rules: Vec<Rule>,
+    overridden: Vec<Method>,
+}
+
+impl HeaderRewriter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the header `name` to `value`, replacing all the existing values.
+    #[must_use]
+    pub fn set(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.rules.push(Rule::Set(name, value));
+        self
+    }
+
+    /// Appends `value` to the header `name`, keeping the existing values.
+    #[must_use]
+    pub fn append(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.rules.push(Rule::Append(name, value));
+        self
+    }
+
+    /// Removes all the values of the header `name`.
+    #[must_use]
+    pub fn remove(mut self, name: HeaderName) -> Self {
+        self.rules.push(Rule::Remove(name));
+        self
+    }
+
+    /// Moves all the values of the header `from` to `to`, appended to its existing values.
+    #[must_use]
+    pub fn rename(mut self, from: HeaderName, to: HeaderName) -> Self {
+        self.rules.push(Rule::Rename(from, to));
+        self
+    }
+
+    /// Replaces all matches of `re` in the values of the header `name` with `rep`, as
+    /// [`RegexAll`](super::RegexAll) does.
+    ///
+    /// The values that are not visible ASCII, or would not be valid anymore, are kept as is.
+    #[must_use]
+    pub fn replace<S: Into<String>>(mut self, name: HeaderName, re: LibRegex, rep: S) -> Self {
+        self.rules.push(Rule::Replace(name, re, rep.into()));
+        self
+    }
+
+    /// Sends the requests with one of `methods` as `POST`, with the original method in the
+    /// `x-http-method-override` header, for the upstreams accepting only `POST`.
+    ///
+    /// This has no effect on the responses.
+    #[must_use]
+    pub fn override_method<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.overridden.extend(methods);
+        self
+    }
+
+    /// Applies the rules to `headers`.
+    pub fn rewrite_headers(&self, headers: &mut HeaderMap) {
+        for rule in &self.rules {
+            match rule {
+                Rule::Set(name, value) => {
+                    headers.insert(name, value.clone());
+                },
+                Rule::Append(name, value) => {
+                    headers.append(name, value.clone());
+                },
+                Rule::Remove(name) => {
+                    headers.remove(name);
+                },
+                Rule::Rename(from, to) => {
+                    if let Entry::Occupied(entry) = headers.entry(from) {
+                        let (_, values) = entry.remove_entry_mult();
+                        let values: Vec<_> = values.collect();
+                        for value in values {
+                            headers.append(to, value);
+                        }
+                    }
+                },
+                Rule::Replace(name, re, rep) => {
+                    if let Entry::Occupied(mut entry) = headers.entry(name) {
+                        for value in entry.iter_mut() {
+                            replace(value, re, rep);
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    /// Overrides the method of `request` if needed, and applies the rules to its headers.
+    pub fn rewrite_request<B>(&self, request: &mut Request<B>) {
+        if self.overridden.contains(request.method()) {
+            let method = std::mem::replace(request.method_mut(), Method::POST);
+            if let Ok(value) = HeaderValue::from_str(method.as_str()) {
+                request.headers_mut().insert(METHOD_OVERRIDE, value);
+            }
+        }
+        self.rewrite_headers(request.headers_mut());
+    }
+
+    /// Applies the rules to the headers of `response`.
+    pub fn rewrite_response<B>(&self, response: &mut Response<B>) {
+        self.rewrite_headers(response.headers_mut());
+    }
+}
+
+/// Replaces all matches of `re` in `value` with `rep`.
+fn replace(value: &mut HeaderValue, re: &LibRegex, rep: &str) {
+    let Ok(s) = value.to_str() else {
+        return;
+    };
+    if let Cow::Owned(replaced) = re.replace_all(s, rep) {
+        match HeaderValue::try_from(replaced) {
+            Ok(replaced) => *value = replaced,
+            Err(e) => log::warn!("Cannot replace the header value: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rewrite_headers() {
+        let a = HeaderName::from_static("a");
+        let b = HeaderName::from_static("b");
+        let rw = HeaderRewriter::new()
+            .append(a.clone(), HeaderValue::from_static("3"))
+            .rename(a.clone(), b.clone())
+            .replace(b.clone(), LibRegex::new(r"\d").unwrap(), "<$0>")
+            .replace(b.clone(), LibRegex::new("3").unwrap(), "\n")
+            .set(a.clone(), HeaderValue::from_static("4"))
+            .remove(HeaderName::from_static("c"));
+
+        let mut headers = HeaderMap::new();
+        headers.append(&a, HeaderValue::from_static("1"));
+        headers.append(&a, HeaderValue::from_static("2"));
+        headers.append(&b, HeaderValue::from_static("0"));
+        headers.append("c", HeaderValue::from_static("5"));
+        rw.rewrite_headers(&mut headers);
+
+        let values: Vec<_> = headers.get_all(&b).iter().collect();
+        assert_eq!(values, ["<0>", "<1>", "<2>", "<3>"]);
+        assert_eq!(headers[&a], "4");
+        assert!(!headers.contains_key("c"));
+    }
+
+    #[test]
+    fn override_method() {
+        let rw = HeaderRewriter::new().override_method([Method::PATCH]);
+
+        let mut request = Request::patch("/").body(()).unwrap();
+        rw.rewrite_request(&mut request);
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.headers()[METHOD_OVERRIDE], "PATCH");
+
+        let mut request = Request::delete("/").body(()).unwrap();
+        rw.rewrite_request(&mut request);
+        assert_eq!(request.method(), Method::DELETE);
+        assert!(!request.headers().contains_key(METHOD_OVERRIDE));
+    }
+}
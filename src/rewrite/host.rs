@@ -0,0 +1,190 @@
+use http::uri::{Authority, Scheme};
+use http::Request;
+use regex::Regex as LibRegex;
+
+use super::normalize::remove_dot_segments;
+use super::{RequestRewriter, UriRewriter};
+use crate::Error;
+
+/// `WithTarget(target, rewriter)` rewrites the request with `rewriter` toward the upstream chosen
+/// by `target`.
+///
+/// The request is sent to the scheme and authority of the service if `target` chooses none. All
+/// the upstreams share the client, and its connection pool, of the service, so the schemes chosen
+/// by `target` must be ones its connector supports: an `https` target needs a TLS client, and an
+/// HTTP-only client fails to connect to it.
+///
+/// ```
+/// # use axum_proxy::rewrite::{HostMap, RequestRewriter, TrimPrefix, WithTarget};
+/// # use http::uri::{Authority, Scheme};
+/// # use http::Request;
+/// let hosts = HostMap::new().prefix("/eu", Scheme::HTTP, Authority::from_static("eu.backend"));
+/// let mut rw = WithTarget(hosts, TrimPrefix("/eu"));
+///
+/// let mut request = Request::get("/eu/users").body(()).unwrap();
+/// rw.rewrite_request(&mut request, &Scheme::HTTP, &Authority::from_static("backend"))
+///     .unwrap();
+/// assert_eq!(request.uri(), "http://eu.backend/users");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithTarget<U, P>(pub U, pub P);
+
+impl<U: UriRewriter, P: RequestRewriter> RequestRewriter for WithTarget<U, P> {
+    fn rewrite_request<B>(
+        &mut self,
+        request: &mut Request<B>,
+        scheme: &Scheme,
+        authority: &Authority,
+    ) -> Result<(), Error> {
+        match self.0.rewrite_target(request) {
+            Some((scheme, authority)) => self.1.rewrite_request(request, &scheme, &authority),
+            None => self.1.rewrite_request(request, scheme, authority),
+        }
+    }
+}
+
+/// A condition on the path of a [`HostMap`] entry.
+#[derive(Debug, Clone)]
+enum Route {
+    Prefix(String),
+    Regex(LibRegex),
+}
+
+impl Route {
+    fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Prefix(prefix) => path
+                .strip_prefix(prefix.trim_end_matches('/'))
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+            Self::Regex(re) => re.is_match(path),
+        }
+    }
+}
+
+/// Chooses the upstream by the path of the request, for [`WithTarget`].
+///
+/// The entries are tried in order, and the first one matching the path wins. A prefix matches
+/// whole segments, so `/eu` matches `/eu` and `/eu/users` but not `/europe`.
+///
+/// The path is matched once its dot segments are removed and its duplicate slashes collapsed, as
+/// [`Normalize`](super::Normalize) does by default, so that `/eu/../us/users` is sent to the
+/// upstream of `/us` even if the rewriter of [`WithTarget`] normalizes the path afterwards.
+///
+/// As for [`WithTarget`], the schemes must match what the client of the service can connect to.
+///
+/// ```
+/// # use axum_proxy::rewrite::{HostMap, UriRewriter};
+/// # use http::uri::{Authority, Scheme};
+/// # use http::Request;
+/// # use regex::Regex;
+/// let mut hosts = HostMap::new()
+///     .prefix("/eu", Scheme::HTTP, Authority::from_static("eu.backend"))
+///     .regex(
+///         Regex::new("^/(us|ca)/").unwrap(),
+///         Scheme::HTTP,
+///         Authority::from_static("us.backend:8080"),
+///     );
+///
+/// let request = Request::get("/ca/users").body(()).unwrap();
+/// let (scheme, authority) = hosts.rewrite_target(&request).unwrap();
+/// assert_eq!((scheme.as_str(), authority.as_str()), ("http", "us.backend:8080"));
+///
+/// let request = Request::get("/europe").body(()).unwrap();
+/// assert!(hosts.rewrite_target(&request).is_none());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HostMap {
+    entries: Vec<(Route, Scheme, Authority)>,
+}
+
+impl HostMap {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends the requests whose path is under `prefix` to `scheme://authority`.
+    #[must_use]
+    pub fn prefix<S: Into<String>>(
+        mut self,
+        prefix: S,
+        scheme: Scheme,
+        authority: Authority,
+    ) -> Self {
+        self.entries
+            .push((Route::Prefix(prefix.into()), scheme, authority));
+        self
+    }
+
+    /// Sends the requests whose path matches `re` to `scheme://authority`.
+    #[must_use]
+    pub fn regex(mut self, re: LibRegex, scheme: Scheme, authority: Authority) -> Self {
+        self.entries.push((Route::Regex(re), scheme, authority));
+        self
+    }
+}
+
+impl UriRewriter for HostMap {
+    fn rewrite_target<B>(&mut self, request: &Request<B>) -> Option<(Scheme, Authority)> {
+        let path = remove_dot_segments(request.uri().path(), true);
+        self.entries
+            .iter()
+            .find(|(route, _, _)| route.matches(&path))
+            .map(|(_, scheme, authority)| (scheme.clone(), authority.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rewrite::{Identity, Normalize};
+
+    #[test]
+    fn host_map() {
+        let eu = Authority::from_static("eu.backend");
+        let us = Authority::from_static("us.backend");
+        let mut rw = WithTarget(
+            HostMap::new().prefix("/eu/", Scheme::HTTPS, eu).regex(
+                LibRegex::new("^/(us|eu)").unwrap(),
+                Scheme::HTTP,
+                us,
+            ),
+            Identity,
+        );
+        let authority = Authority::from_static("backend");
+        let mut rewrite = |uri| {
+            let mut request = Request::get(uri).body(()).unwrap();
+            rw.rewrite_request(&mut request, &Scheme::HTTP, &authority)
+                .unwrap();
+            request.uri().to_string()
+        };
+        assert_eq!(rewrite("/eu"), "https://eu.backend/eu");
+        assert_eq!(rewrite("/eu/a?b"), "https://eu.backend/eu/a?b");
+        assert_eq!(rewrite("/europe"), "http://us.backend/europe");
+        assert_eq!(rewrite("/us/a"), "http://us.backend/us/a");
+        assert_eq!(rewrite("/asia"), "http://backend/asia");
+    }
+
+    #[test]
+    fn normalized_path() {
+        let eu = Authority::from_static("eu.backend");
+        let us = Authority::from_static("us.backend");
+        let mut rw = WithTarget(
+            HostMap::new()
+                .prefix("/eu", Scheme::HTTP, eu)
+                .prefix("/us", Scheme::HTTP, us),
+            Normalize::new(Identity),
+        );
+        let authority = Authority::from_static("backend");
+        let mut rewrite = |uri| {
+            let mut request = Request::get(uri).body(()).unwrap();
+            rw.rewrite_request(&mut request, &Scheme::HTTP, &authority)
+                .unwrap();
+            request.uri().to_string()
+        };
+        assert_eq!(rewrite("/eu/../us/x"), "http://us.backend/us/x");
+        assert_eq!(rewrite("/eu/%2E%2E/us/x"), "http://us.backend/us/x");
+        assert_eq!(rewrite("/us/./..//eu/x"), "http://eu.backend/eu/x");
+        assert_eq!(rewrite("/eu/../asia"), "http://backend/asia");
+    }
+}
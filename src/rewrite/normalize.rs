@@ -0,0 +1,305 @@
+use std::borrow::Cow;
+
+use http::uri::{Authority, PathAndQuery, Scheme, Uri};
+use http::Request;
+
+use super::RequestRewriter;
+use crate::Error;
+
+/// How [`Normalize`] handles encoded slashes `%2F` in the path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncodedSlashes {
+    /// Keeps them as part of the segment.
+    ///
+    /// This gives no protection against a traversal such as `..%2F..%2Fadmin` if the upstream
+    /// decodes them, unless the path is checked by [`Confine`] as well.
+    Keep,
+    /// Decodes them to `/` before normalizing, so they separate segments.
+    Decode,
+    /// Rejects the request with [`Error::InvalidPath`].
+    #[default]
+    Reject,
+}
+
+/// Returns the number of dots if `segment` is `.` or `..`, even if they are percent-encoded.
+fn dot_segment(segment: &str) -> Option<usize> {
+    let mut rest = segment;
+    let mut dots = 0;
+    while !rest.is_empty() {
+        rest = rest
+            .strip_prefix('.')
+            .or_else(|| rest.strip_prefix("%2E"))
+            .or_else(|| rest.strip_prefix("%2e"))?;
+        dots += 1;
+    }
+    (1..=2).contains(&dots).then_some(dots)
+}
+
+/// Removes the dot segments of `path` as RFC 3986 does, collapsing empty segments as well if
+/// `merge_slashes`.
+pub(super) fn remove_dot_segments(path: &str, merge_slashes: bool) -> Cow<'_, str> {
+    let Some(rest) = path.strip_prefix('/') else {
+        return path.into();
+    };
+    let mut segments = rest.split('/').peekable();
+    let mut output = Vec::new();
+    while let Some(segment) = segments.next() {
+        let last = segments.peek().is_none();
+        match dot_segment(segment) {
+            Some(1) => {},
+            Some(_) => {
+                output.pop();
+            },
+            None if merge_slashes && segment.is_empty() && !last => continue,
+            None => {
+                output.push(segment);
+                continue;
+            },
+        }
+        // A dot segment at the end leaves the trailing slash.
+        if last {
+            output.push("");
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len());
+    for segment in output {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if normalized == path {
+        path.into()
+    } else {
+        normalized.into()
+    }
+}
+
+/// Returns whether `path` has a segment such as `%2F` that may be decoded to `/` by the upstream.
+fn has_encoded_slash(path: &str) -> bool {
+    path.contains("%2F") || path.contains("%2f")
+}
+
+/// Normalizes the path of the request before rewriting it with the inner rewriter.
+///
+/// The dot segments `.` and `..` are removed as RFC 3986 does, even if the dots are
+/// percent-encoded, and the empty segments of duplicate slashes are collapsed unless disabled by
+/// [`merge_slashes()`](Self::merge_slashes). Encoded slashes are handled by
+/// [`encoded_slashes()`](Self::encoded_slashes), which rejects them by default.
+///
+/// Rewriters such as [`TrimPrefix`](super::TrimPrefix) work on the raw path, so `/users/../admin`
+/// would escape the intended subtree of the upstream. Combine this with [`Confine`] to be sure.
+///
+/// ```
+/// # use axum_proxy::rewrite::{EncodedSlashes, Normalize, RequestRewriter, TrimPrefix};
+/// # use http::uri::{Authority, Scheme};
+/// # use http::Request;
+/// let mut rw = Normalize::new(TrimPrefix("/api")).encoded_slashes(EncodedSlashes::Decode);
+/// let mut request = Request::get("/api//users/.%2E/admin%2Fposts?a=1").body(()).unwrap();
+/// rw.rewrite_request(&mut request, &Scheme::HTTP, &Authority::from_static("backend"))
+///     .unwrap();
+/// assert_eq!(request.uri(), "http://backend/admin/posts?a=1");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Normalize<P> {
+    inner: P,
+    merge_slashes: bool,
+    encoded_slashes: EncodedSlashes,
+}
+
+impl<P> Normalize<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            merge_slashes: true,
+            encoded_slashes: EncodedSlashes::default(),
+        }
+    }
+
+    /// Whether to collapse duplicate slashes such as `//`. Defaults to `true`.
+    #[must_use]
+    pub fn merge_slashes(mut self, merge_slashes: bool) -> Self {
+        self.merge_slashes = merge_slashes;
+        self
+    }
+
+    /// How to handle encoded slashes. Defaults to [`EncodedSlashes::Reject`].
+    #[must_use]
+    pub fn encoded_slashes(mut self, encoded_slashes: EncodedSlashes) -> Self {
+        self.encoded_slashes = encoded_slashes;
+        self
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    /// Normalizes `path`.
+    ///
+    /// # Errors
+    ///
+    /// When `path` has an encoded slash and they are rejected.
+    pub fn normalize<'a>(&self, path: &'a str) -> Result<Cow<'a, str>, Error> {
+        let path: Cow<'_, str> = match self.encoded_slashes {
+            EncodedSlashes::Decode if has_encoded_slash(path) => {
+                path.replace("%2F", "/").replace("%2f", "/").into()
+            },
+            EncodedSlashes::Reject if has_encoded_slash(path) => {
+                return Err(Error::InvalidPath(path.to_owned()));
+            },
+            _ => path.into(),
+        };
+        Ok(match path {
+            Cow::Borrowed(path) => remove_dot_segments(path, self.merge_slashes),
+            Cow::Owned(path) => remove_dot_segments(&path, self.merge_slashes)
+                .into_owned()
+                .into(),
+        })
+    }
+}
+
+impl<P: RequestRewriter> RequestRewriter for Normalize<P> {
+    fn rewrite_request<B>(
+        &mut self,
+        request: &mut Request<B>,
+        scheme: &Scheme,
+        authority: &Authority,
+    ) -> Result<(), Error> {
+        let uri = request.uri();
+        if let Cow::Owned(path) = self.normalize(uri.path())? {
+            let path_and_query = match uri.query() {
+                Some(query) => format!("{path}?{query}"),
+                None => path,
+            };
+            let mut parts = uri.clone().into_parts();
+            parts.path_and_query = Some(
+                PathAndQuery::try_from(path_and_query).map_err(|e| Error::InvalidUri(e.into()))?,
+            );
+            *request.uri_mut() = Uri::from_parts(parts).map_err(|e| Error::InvalidUri(e.into()))?;
+        }
+        self.inner.rewrite_request(request, scheme, authority)
+    }
+}
+
+/// `Confine(prefix, rewriter)` rejects the request with [`Error::InvalidPath`] unless the path
+/// rewritten by `rewriter` stays under `prefix`.
+///
+/// The rewritten path is outside if it does not start with the segments of `prefix`, or has a
+/// dot segment, even a percent-encoded one or after an encoded slash or a backslash, which the
+/// upstream may resolve out of `prefix`. With the `axum` feature, the error is rendered as
+/// `400 Bad Request`.
+///
+/// ```
+/// # use axum_proxy::rewrite::{AppendPrefix, Confine, RequestRewriter};
+/// # use axum_proxy::Error;
+/// # use http::uri::{Authority, Scheme};
+/// # use http::Request;
+/// let mut rw = Confine("/users", AppendPrefix("/users"));
+/// let (scheme, authority) = (Scheme::HTTP, Authority::from_static("backend"));
+///
+/// let mut request = Request::get("/42").body(()).unwrap();
+/// rw.rewrite_request(&mut request, &scheme, &authority).unwrap();
+/// assert_eq!(request.uri(), "http://backend/users/42");
+///
+/// let mut request = Request::get("/..%2Fadmin").body(()).unwrap();
+/// let result = rw.rewrite_request(&mut request, &scheme, &authority);
+/// assert!(matches!(result, Err(Error::InvalidPath(_))));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Confine<S, P>(pub S, pub P);
+
+impl<S: AsRef<str>, P> Confine<S, P> {
+    /// Returns whether `path` is under the prefix.
+    pub fn contains(&self, path: &str) -> bool {
+        let prefix = self.0.as_ref().trim_end_matches('/');
+        let under = path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+        let escapes = path
+            .split(['/', '\\'])
+            .flat_map(|s| s.split("%2F").flat_map(|s| s.split("%2f")))
+            .flat_map(|s| s.split("%5C").flat_map(|s| s.split("%5c")))
+            .any(|segment| dot_segment(segment).is_some());
+        under && !escapes
+    }
+}
+
+impl<S: AsRef<str>, P: RequestRewriter> RequestRewriter for Confine<S, P> {
+    fn rewrite_request<B>(
+        &mut self,
+        request: &mut Request<B>,
+        scheme: &Scheme,
+        authority: &Authority,
+    ) -> Result<(), Error> {
+        self.1.rewrite_request(request, scheme, authority)?;
+        let path = request.uri().path();
+        if self.contains(path) {
+            Ok(())
+        } else {
+            Err(Error::InvalidPath(path.to_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rewrite::{Identity, TrimPrefix};
+
+    #[test]
+    fn dot_segments() {
+        let normalize = |path| remove_dot_segments(path, false).into_owned();
+        assert_eq!(normalize("/a/b/c/./../../g"), "/a/g");
+        assert_eq!(normalize("/mid/content=5/../6"), "/mid/6");
+        assert_eq!(normalize("/a/b/.."), "/a/");
+        assert_eq!(normalize("/a/."), "/a/");
+        assert_eq!(normalize("/../../a"), "/a");
+        assert_eq!(normalize("/a//../b"), "/a/b");
+        assert_eq!(normalize("/a/%2e%2E/b/.../%2e"), "/b/.../");
+        assert_eq!(normalize("*"), "*");
+        assert!(matches!(
+            remove_dot_segments("/a//b/", false),
+            Cow::Borrowed("/a//b/")
+        ));
+
+        assert_eq!(remove_dot_segments("//a//b//", true), "/a/b/");
+        assert_eq!(remove_dot_segments("/a//..//b", true), "/b");
+    }
+
+    #[test]
+    fn encoded_slashes() {
+        let normalize = Normalize::new(Identity);
+        assert!(matches!(
+            normalize.normalize("/a/..%2Fb"),
+            Err(Error::InvalidPath(p)) if p == "/a/..%2Fb"
+        ));
+        assert_eq!(normalize.normalize("/a/../b").unwrap(), "/b");
+        let normalize = normalize.encoded_slashes(EncodedSlashes::Decode);
+        assert_eq!(normalize.normalize("/a/..%2fb").unwrap(), "/b");
+        let normalize = normalize.encoded_slashes(EncodedSlashes::Keep);
+        assert_eq!(normalize.normalize("/a/..%2Fb").unwrap(), "/a/..%2Fb");
+    }
+
+    #[test]
+    fn confine() {
+        let confine = Confine("/users/", Identity);
+        assert!(confine.contains("/users"));
+        assert!(confine.contains("/users/42/posts"));
+        assert!(!confine.contains("/users2"));
+        assert!(!confine.contains("/admin"));
+        assert!(!confine.contains("/users/../admin"));
+        assert!(!confine.contains("/users/%2e%2e%2Fadmin"));
+        assert!(!confine.contains("/users/..\\admin"));
+        assert!(Confine("/", Identity).contains("/admin"));
+
+        let mut rw = Confine("/api", Normalize::new(TrimPrefix("/public")));
+        let (scheme, authority) = (Scheme::HTTP, Authority::from_static("backend"));
+        let mut request = Request::get("/public/api/../api/users").body(()).unwrap();
+        rw.rewrite_request(&mut request, &scheme, &authority)
+            .unwrap();
+        assert_eq!(request.uri(), "http://backend/api/users");
+
+        let mut request = Request::get("/public/api/../admin").body(()).unwrap();
+        let result = rw.rewrite_request(&mut request, &scheme, &authority);
+        assert!(matches!(result, Err(Error::InvalidPath(p)) if p == "/admin"));
+    }
+}
@@ -0,0 +1,264 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use regex::Regex as LibRegex;
+
+use super::PathRewriter;
+
+/// Represents a rule to rewrite a query `foo=1&bar=2` to new one.
+///
+/// The rule works on the decoded key/value pairs of the query, in order. They are decoded and
+/// encoded as `application/x-www-form-urlencoded`, so `+` is a space. If the pairs are left as is,
+/// the query is kept verbatim.
+pub trait QueryRewriter {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>);
+
+    /// Rewrites the encoded `query`, without the leading `?`.
+    fn rewrite_query<'a>(&mut self, query: &'a str) -> Cow<'a, str> {
+        let original: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+        let mut pairs = original.clone();
+        self.rewrite_pairs(&mut pairs);
+        if pairs == original {
+            return query.into();
+        }
+
+        form_urlencoded::Serializer::new(String::with_capacity(query.len()))
+            .extend_pairs(pairs)
+            .finish()
+            .into()
+    }
+}
+
+/// `WithQuery(path, query)` rewrites the path with `path` and the query with `query`.
+///
+/// This is usually made by [`PathRewriter::with_query()`]. The query is rewritten by `path` first,
+/// so `WithQuery`s can be nested.
+///
+/// ```
+/// # use axum_proxy::rewrite::{PathRewriter, RenameQuery, TrimPrefix, WithQuery};
+/// let mut rw = WithQuery(TrimPrefix("/search"), RenameQuery("q", "query"));
+/// assert_eq!(rw.rewrite("/search/users"), "/users");
+/// assert_eq!(rw.rewrite_query("q=rust&page=2"), "query=rust&page=2");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithQuery<P, Q>(pub P, pub Q);
+
+impl<P: PathRewriter, Q: QueryRewriter> PathRewriter for WithQuery<P, Q> {
+    fn rewrite<'a>(&'a mut self, path: &'a str) -> Cow<'a, str> {
+        self.0.rewrite(path)
+    }
+
+    fn rewrite_query<'a>(&'a mut self, query: &'a str) -> Cow<'a, str> {
+        match self.0.rewrite_query(query) {
+            Cow::Borrowed(query) => self.1.rewrite_query(query),
+            Cow::Owned(query) => self.1.rewrite_query(&query).into_owned().into(),
+        }
+    }
+}
+
+/// `AddQuery(key, value)` appends a pair, even if `key` already exists.
+///
+/// ```
+/// # use axum_proxy::rewrite::{QueryRewriter, AddQuery};
+/// assert_eq!(AddQuery("key", "a b").rewrite_query("key=c"), "key=c&key=a+b");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddQuery<K, V = K>(pub K, pub V);
+
+impl<K: AsRef<str>, V: AsRef<str>> QueryRewriter for AddQuery<K, V> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        pairs.push((self.0.as_ref().to_owned(), self.1.as_ref().to_owned()));
+    }
+}
+
+/// `SetQuery(key, value)` sets the value of `key`, appending it if not exists.
+///
+/// The first pair with `key` is replaced, and the others are removed.
+///
+/// ```
+/// # use axum_proxy::rewrite::{QueryRewriter, SetQuery};
+/// assert_eq!(SetQuery("a", "1").rewrite_query("a=0&b=2&a=3"), "a=1&b=2");
+/// assert_eq!(SetQuery("a", "1").rewrite_query("b=2"), "b=2&a=1");
+/// assert_eq!(SetQuery("page", 2.to_string()).rewrite_query(""), "page=2");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetQuery<K, V = K>(pub K, pub V);
+
+impl<K: AsRef<str>, V: AsRef<str>> QueryRewriter for SetQuery<K, V> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        let (k, v) = (self.0.as_ref(), self.1.as_ref());
+        let mut found = false;
+        pairs.retain_mut(|(key, value)| {
+            if key != k {
+                return true;
+            }
+            if found {
+                return false;
+            }
+            found = true;
+            v.clone_into(value);
+            true
+        });
+        if !found {
+            pairs.push((k.to_owned(), v.to_owned()));
+        }
+    }
+}
+
+/// `RemoveQuery(key)` removes all the pairs with `key`.
+///
+/// ```
+/// # use axum_proxy::rewrite::{QueryRewriter, RemoveQuery};
+/// assert_eq!(RemoveQuery("a").rewrite_query("a=0&b=2&a=3"), "b=2");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoveQuery<S>(pub S);
+
+impl<S: AsRef<str>> QueryRewriter for RemoveQuery<S> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        pairs.retain(|(key, _)| key != self.0.as_ref());
+    }
+}
+
+/// `RenameQuery(old, new)` renames the key `old` to `new`.
+///
+/// ```
+/// # use axum_proxy::rewrite::{QueryRewriter, RenameQuery};
+/// assert_eq!(RenameQuery("q", "query").rewrite_query("q=a&p=1"), "query=a&p=1");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameQuery<K, V = K>(pub K, pub V);
+
+impl<K: AsRef<str>, V: AsRef<str>> QueryRewriter for RenameQuery<K, V> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        let (old, new) = (self.0.as_ref(), self.1.as_ref());
+        for (key, _) in pairs.iter_mut().filter(|(key, _)| key == old) {
+            new.clone_into(key);
+        }
+    }
+}
+
+/// `RemoveQueryRegex(re)` removes the pairs whose key matches `re`.
+///
+/// ```
+/// # use axum_proxy::rewrite::{QueryRewriter, RemoveQueryRegex};
+/// # use regex::Regex;
+/// let re = Regex::new("^utm_").unwrap();
+/// assert_eq!(
+///     RemoveQueryRegex(re).rewrite_query("id=1&utm_source=a&utm_medium=b"),
+///     "id=1"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct RemoveQueryRegex(pub LibRegex);
+
+impl QueryRewriter for RemoveQueryRegex {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        pairs.retain(|(key, _)| !self.0.is_match(key));
+    }
+}
+
+/// `AllowQuery(keys)` removes the pairs whose key is not in `keys`.
+///
+/// `keys` is a slice, an array or a `Vec` of `&str`, `String` or the like, or an `Arc<[_]>`.
+///
+/// ```
+/// # use axum_proxy::rewrite::{QueryRewriter, AllowQuery};
+/// assert_eq!(AllowQuery(&["a", "c"]).rewrite_query("a=1&b=2&c=3"), "a=1&c=3");
+/// assert_eq!(AllowQuery(vec![String::from("b")]).rewrite_query("a=1&b=2"), "b=2");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllowQuery<L>(pub L);
+
+/// Keeps the pairs whose key is in `keys`.
+fn allow<S: AsRef<str>>(keys: &[S], pairs: &mut Vec<(String, String)>) {
+    pairs.retain(|(key, _)| keys.iter().any(|k| k.as_ref() == key));
+}
+
+impl<S: AsRef<str>> QueryRewriter for AllowQuery<&[S]> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        allow(self.0, pairs);
+    }
+}
+
+impl<S: AsRef<str>, const N: usize> QueryRewriter for AllowQuery<&[S; N]> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        allow(self.0, pairs);
+    }
+}
+
+impl<S: AsRef<str>, const N: usize> QueryRewriter for AllowQuery<[S; N]> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        allow(&self.0, pairs);
+    }
+}
+
+impl<S: AsRef<str>> QueryRewriter for AllowQuery<Vec<S>> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        allow(&self.0, pairs);
+    }
+}
+
+impl<S: AsRef<str>> QueryRewriter for AllowQuery<Arc<[S]>> {
+    fn rewrite_pairs(&mut self, pairs: &mut Vec<(String, String)>) {
+        allow(&self.0, pairs);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::Request;
+
+    use super::*;
+    use crate::rewrite::{Identity, TrimPrefix};
+
+    #[test]
+    fn encoding() {
+        // Untouched queries are kept verbatim, even if they are not canonical.
+        let query = "a=%7e&b=c%20d";
+        assert!(matches!(
+            RemoveQuery("x").rewrite_query(query),
+            Cow::Borrowed(q) if q == query
+        ));
+
+        assert_eq!(
+            SetQuery("q", "caf\u{e9} & co").rewrite_query("q=old&r=a%2Bb"),
+            "q=caf%C3%A9+%26+co&r=a%2Bb"
+        );
+        assert_eq!(
+            RenameQuery("k y", "key").rewrite_query("k+y=1&k%20y=2"),
+            "key=1&key=2"
+        );
+        assert_eq!(RemoveQuery("a").rewrite_query("a=1"), "");
+    }
+
+    #[test]
+    fn rewrite_uri() {
+        fn rewrite(rw: &mut impl PathRewriter, uri: &str) -> String {
+            let scheme = "http".try_into().unwrap();
+            let authority = "example.com".try_into().unwrap();
+            let mut request = Request::get(uri).body(()).unwrap();
+            rw.rewrite_uri(&mut request, &scheme, &authority).unwrap();
+            request.uri().to_string()
+        }
+
+        let mut rw = TrimPrefix("/api")
+            .with_query(AddQuery("key", "secret"))
+            .with_query(RemoveQuery("debug"));
+        assert_eq!(
+            rewrite(&mut rw, "/api/foo?debug=1"),
+            "http://example.com/foo?key=secret"
+        );
+        assert_eq!(
+            rewrite(&mut rw, "/api/foo"),
+            "http://example.com/foo?key=secret"
+        );
+
+        let mut rw = Identity.with_query(RemoveQuery("debug"));
+        assert_eq!(rewrite(&mut rw, "/foo?debug=1"), "http://example.com/foo");
+        assert_eq!(rewrite(&mut rw, "/foo?"), "http://example.com/foo?");
+        assert_eq!(rewrite(&mut rw, "/foo"), "http://example.com/foo");
+    }
+}
@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use regex::{Error as RegexError, Regex as LibRegex};
+use serde::Deserialize;
+
+use super::combinator::then;
+use super::{
+    impl_path_rewriter, AppendPrefix, AppendSuffix, PathRewriter, RegexAll, RegexN, ReplaceAll,
+    ReplaceN, SharedRewriter, Static, TrimPrefix, TrimSuffix,
+};
+
+/// A rewriter read from a configuration, mirroring the built-in one of the same name.
+///
+/// The regexes are compiled when the rules are loaded into [`RewriteRules`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum RewriteRule {
+    Static(String),
+    ReplaceAll(String, String),
+    ReplaceN(String, String, usize),
+    TrimPrefix(String),
+    TrimSuffix(String),
+    AppendPrefix(String),
+    AppendSuffix(String),
+    RegexAll(String, String),
+    RegexN(String, String, usize),
+}
+
+/// A [`RewriteRule`] ready to rewrite.
+#[derive(Debug, Clone)]
+enum Compiled {
+    Static(Static<String>),
+    ReplaceAll(ReplaceAll<String>),
+    ReplaceN(ReplaceN<String>),
+    TrimPrefix(TrimPrefix<String>),
+    TrimSuffix(TrimSuffix<String>),
+    AppendPrefix(AppendPrefix<String>),
+    AppendSuffix(AppendSuffix<String>),
+    RegexAll(RegexAll<String>),
+    RegexN(RegexN<String>),
+}
+
+impl TryFrom<RewriteRule> for Compiled {
+    type Error = RegexError;
+
+    fn try_from(rule: RewriteRule) -> Result<Self, Self::Error> {
+        Ok(match rule {
+            RewriteRule::Static(path) => Self::Static(Static(path)),
+            RewriteRule::ReplaceAll(old, new) => Self::ReplaceAll(ReplaceAll(old, new)),
+            RewriteRule::ReplaceN(old, new, n) => Self::ReplaceN(ReplaceN(old, new, n)),
+            RewriteRule::TrimPrefix(prefix) => Self::TrimPrefix(TrimPrefix(prefix)),
+            RewriteRule::TrimSuffix(suffix) => Self::TrimSuffix(TrimSuffix(suffix)),
+            RewriteRule::AppendPrefix(prefix) => Self::AppendPrefix(AppendPrefix(prefix)),
+            RewriteRule::AppendSuffix(suffix) => Self::AppendSuffix(AppendSuffix(suffix)),
+            RewriteRule::RegexAll(re, new) => Self::RegexAll(RegexAll(LibRegex::new(&re)?, new)),
+            RewriteRule::RegexN(re, new, n) => Self::RegexN(RegexN(LibRegex::new(&re)?, new, n)),
+        })
+    }
+}
+
+impl SharedRewriter for Compiled {
+    fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::Static(rw) => rw.rewrite_shared(path),
+            Self::ReplaceAll(rw) => rw.rewrite_shared(path),
+            Self::ReplaceN(rw) => rw.rewrite_shared(path),
+            Self::TrimPrefix(rw) => rw.rewrite_shared(path),
+            Self::TrimSuffix(rw) => rw.rewrite_shared(path),
+            Self::AppendPrefix(rw) => rw.rewrite_shared(path),
+            Self::AppendSuffix(rw) => rw.rewrite_shared(path),
+            Self::RegexAll(rw) => rw.rewrite_shared(path),
+            Self::RegexN(rw) => rw.rewrite_shared(path),
+        }
+    }
+}
+
+/// A list of [`RewriteRule`]s, applied in order.
+///
+/// This is deserialized from a list of rules, such as in TOML:
+///
+/// ```
+/// # use axum_proxy::rewrite::{PathRewriter, RewriteRules};
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Config {
+///     rules: RewriteRules,
+/// }
+///
+/// let config: Config = toml::from_str(
+///     r#"
+///     rules = [
+///         { TrimPrefix = "/api" },
+///         { RegexAll = ['^/users/(\d+)$', "/accounts/$1"] },
+///         { ReplaceN = ["-", "_", 1] },
+///     ]
+///     "#,
+/// )
+/// .unwrap();
+/// let mut rw = config.rules;
+/// assert_eq!(rw.rewrite("/api/users/42"), "/accounts/42");
+/// assert_eq!(rw.rewrite("/api/a-b-c"), "/a_b-c");
+/// ```
+///
+/// The regexes are compiled when loaded, and an invalid one fails with [`InvalidRule`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Vec<RewriteRule>")]
+pub struct RewriteRules(Vec<Compiled>);
+
+impl RewriteRules {
+    /// # Errors
+    ///
+    /// When a rule has an invalid regex.
+    pub fn new<I: IntoIterator<Item = RewriteRule>>(rules: I) -> Result<Self, InvalidRule> {
+        rules
+            .into_iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                Compiled::try_from(rule).map_err(|reason| InvalidRule { index, reason })
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl TryFrom<Vec<RewriteRule>> for RewriteRules {
+    type Error = InvalidRule;
+
+    fn try_from(rules: Vec<RewriteRule>) -> Result<Self, Self::Error> {
+        Self::new(rules)
+    }
+}
+
+impl SharedRewriter for RewriteRules {
+    fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str> {
+        self.0.iter().fold(path.into(), |path, rule| {
+            then(path, move |path, _| rule.rewrite_shared(path))
+        })
+    }
+}
+
+impl_path_rewriter!(RewriteRules);
+
+/// The `index`-th [`RewriteRule`] has an invalid regex.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidRule {
+    pub index: usize,
+    pub reason: RegexError,
+}
+
+impl fmt::Display for InvalidRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid rule at index {}: {}", self.index, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidRule {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rules() {
+        let mut rw = RewriteRules::new([
+            RewriteRule::Static("/a-b-c".to_owned()),
+            RewriteRule::ReplaceAll("-".to_owned(), "/".to_owned()),
+            RewriteRule::TrimSuffix("/c".to_owned()),
+            RewriteRule::AppendSuffix("/d".to_owned()),
+            RewriteRule::RegexN("[a-z]".to_owned(), "x".to_owned(), 2),
+        ])
+        .unwrap();
+        assert_eq!(rw.rewrite("/foo"), "/x/x/d");
+
+        let mut rw = RewriteRules::new([RewriteRule::TrimPrefix("/api".to_owned())]).unwrap();
+        assert!(matches!(rw.rewrite("/users"), Cow::Borrowed("/users")));
+        assert!(matches!(rw.rewrite("/api/users"), Cow::Borrowed("/users")));
+    }
+
+    #[test]
+    fn deserialize() {
+        #[derive(Debug, Deserialize)]
+        struct Config {
+            #[expect(dead_code)]
+            rules: RewriteRules,
+        }
+
+        let error = toml::from_str::<Config>(
+            r#"rules = [{ AppendPrefix = "/v2" }, { RegexAll = ["(", "x"] }]"#,
+        )
+        .unwrap_err();
+        assert!(error.message().starts_with("Invalid rule at index 1: "));
+
+        let error = RewriteRules::new([
+            RewriteRule::RegexAll(".".to_owned(), String::new()),
+            RewriteRule::RegexN("[".to_owned(), String::new(), 1),
+        ])
+        .unwrap_err();
+        assert_eq!(error.index, 1);
+
+        assert!(toml::from_str::<Config>(r#"rules = [{ Prepend = "/v2" }]"#).is_err());
+    }
+}
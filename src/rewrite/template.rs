@@ -0,0 +1,295 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, CONTROLS};
+
+use super::{impl_path_rewriter, PathRewriter, SharedRewriter};
+
+/// The characters encoded in a path segment.
+pub(super) const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Re-encodes a raw path segment canonically.
+///
+/// The segment is decoded and encoded as bytes, so one that is not UTF-8 once decoded is kept.
+fn reencode(segment: &str) -> String {
+    let decoded: Vec<u8> = percent_decode_str(segment).collect();
+    percent_encode(&decoded, SEGMENT).to_string()
+}
+
+/// A segment of the pattern of a [`Template`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    /// `{name}`, matching one segment
+    Param(String),
+    /// `{*name}`, matching the rest of the path
+    Wildcard(String),
+}
+
+/// A part of the template of a [`Template`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Param(String),
+}
+
+/// An error in the pattern or the template of a [`Template`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A `{` or `}` is not part of a placeholder.
+    UnbalancedBrace,
+    /// A capture of the pattern is not a whole segment, is empty or duplicated, or a wildcard is
+    /// not at the end.
+    InvalidCapture(String),
+    /// A placeholder of the template is not captured by the pattern.
+    UnknownPlaceholder(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnbalancedBrace => write!(f, "unbalanced brace"),
+            Self::InvalidCapture(segment) => write!(f, "invalid capture: {segment}"),
+            Self::UnknownPlaceholder(name) => write!(f, "unknown placeholder: {{{name}}}"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Rewrites the path matching a route pattern into a template with the captured values.
+///
+/// The pattern has the syntax of axum routes, with `{name}` capturing a segment and `{*name}`
+/// the rest of the path, so the route of the service can be copied as is. The placeholders of the
+/// template are `{name}` or `{*name}`. The captured values are re-encoded canonically, keeping
+/// the slashes between the segments of a wildcard but not encoded ones.
+///
+/// This does not read the captures of the axum router, such as `RawPathParams`, since the
+/// rewriters work without axum: the pattern is matched again by this, duplicating the route, and
+/// the two must be kept in sync. It is matched against the path the service receives, which is
+/// the full path unless the service is nested. A path not matching the pattern is kept as is.
+///
+/// A path capturing a dot segment `.` or `..`, even percent-encoded, does not match either, since
+/// the segment would move the rewritten path out of the template. The path is still sent as is,
+/// so wrap this in [`Normalize`](super::Normalize) to remove them first.
+///
+/// ```
+/// # use axum_proxy::rewrite::{PathRewriter, Template};
+/// let mut rw = Template::new(
+///     "/users/{id}/posts/{*rest}",
+///     "/v2/accounts/{id}/items/{rest}",
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     rw.rewrite("/users/42/posts/a%2fb/c"),
+///     "/v2/accounts/42/items/a%2Fb/c"
+/// );
+/// assert_eq!(rw.rewrite("/users/42"), "/users/42");
+///
+/// assert!(Template::new("/users/{id}", "/accounts/{user}").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pattern: Vec<Segment>,
+    template: Vec<Part>,
+}
+
+impl Template {
+    /// # Errors
+    ///
+    /// When `pattern` or `template` is invalid, or `template` has a placeholder not captured by
+    /// `pattern`.
+    pub fn new(pattern: &str, template: &str) -> Result<Self, TemplateError> {
+        let pattern = parse_pattern(pattern)?;
+        let template = parse_template(template)?;
+        for part in &template {
+            if let Part::Param(name) = part {
+                let captured = pattern.iter().any(|segment| {
+                    matches!(segment, Segment::Param(n) | Segment::Wildcard(n) if n == name)
+                });
+                if !captured {
+                    return Err(TemplateError::UnknownPlaceholder(name.clone()));
+                }
+            }
+        }
+        Ok(Self { pattern, template })
+    }
+
+    /// Matches `path` against the pattern, returning the captured values re-encoded.
+    fn captures<'p>(&'p self, path: &str) -> Option<Vec<(&'p str, String)>> {
+        let capture = |segment| Some(reencode(segment)).filter(|s| s != "." && s != "..");
+        let mut segments = path.strip_prefix('/')?.split('/');
+        let mut captures = Vec::new();
+        for expected in &self.pattern {
+            match expected {
+                Segment::Static(s) => {
+                    if segments.next()? != s {
+                        return None;
+                    }
+                },
+                Segment::Param(name) => {
+                    let value = segments.next().filter(|v| !v.is_empty())?;
+                    captures.push((name.as_str(), capture(value)?));
+                },
+                Segment::Wildcard(name) => {
+                    let rest: Vec<_> = segments.by_ref().map(capture).collect::<Option<_>>()?;
+                    if rest.iter().all(String::is_empty) {
+                        return None;
+                    }
+                    captures.push((name.as_str(), rest.join("/")));
+                },
+            }
+        }
+        segments.next().is_none().then_some(captures)
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Vec<Segment>, TemplateError> {
+    let invalid = |segment: &str| TemplateError::InvalidCapture(segment.to_owned());
+    let mut segments = Vec::new();
+    for segment in pattern.strip_prefix('/').unwrap_or(pattern).split('/') {
+        if matches!(segments.last(), Some(Segment::Wildcard(_))) {
+            return Err(invalid(segment));
+        }
+        let Some(name) = segment.strip_prefix('{') else {
+            if segment.contains(['{', '}']) {
+                return Err(invalid(segment));
+            }
+            segments.push(Segment::Static(segment.to_owned()));
+            continue;
+        };
+        let name = name.strip_suffix('}').ok_or_else(|| invalid(segment))?;
+        let (name, wildcard) = match name.strip_prefix('*') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let duplicated = segments
+            .iter()
+            .any(|s| matches!(s, Segment::Param(n) | Segment::Wildcard(n) if n == name));
+        if name.is_empty() || name.contains(['{', '}', '*']) || duplicated {
+            return Err(invalid(segment));
+        }
+        segments.push(if wildcard {
+            Segment::Wildcard(name.to_owned())
+        } else {
+            Segment::Param(name.to_owned())
+        });
+    }
+    Ok(segments)
+}
+
+fn parse_template(template: &str) -> Result<Vec<Part>, TemplateError> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        let (text, placeholder) = rest.split_at(start);
+        if !text.is_empty() {
+            parts.push(Part::Text(text.to_owned()));
+        }
+        let (name, after) = placeholder
+            .strip_prefix('{')
+            .and_then(|p| p.split_once('}'))
+            .ok_or(TemplateError::UnbalancedBrace)?;
+        let name = name.strip_prefix('*').unwrap_or(name);
+        if name.is_empty() || name.contains('{') {
+            return Err(TemplateError::UnbalancedBrace);
+        }
+        parts.push(Part::Param(name.to_owned()));
+        rest = after;
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest.to_owned()));
+    }
+    Ok(parts)
+}
+
+impl SharedRewriter for Template {
+    fn rewrite_shared<'a>(&'a self, path: &'a str) -> Cow<'a, str> {
+        let Some(captures) = self.captures(path) else {
+            return path.into();
+        };
+        let mut rewritten = String::with_capacity(path.len());
+        for part in &self.template {
+            match part {
+                Part::Text(text) => rewritten.push_str(text),
+                Part::Param(name) => {
+                    if let Some((_, value)) = captures.iter().find(|(n, _)| n == name) {
+                        rewritten.push_str(value);
+                    }
+                },
+            }
+        }
+        rewritten.into()
+    }
+}
+
+impl_path_rewriter!(Template);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rewrite() {
+        let mut rw = Template::new("/files/{dir}/{*path}", "/{dir}.d/{path}").unwrap();
+        assert_eq!(rw.rewrite("/files/a/b/c"), "/a.d/b/c");
+        assert_eq!(rw.rewrite("/files/a%20b/%7e/"), "/a%20b.d/~/");
+        assert_eq!(rw.rewrite("/files/%ff/%C3%A9%fe"), "/%FF.d/%C3%A9%FE");
+        assert_eq!(rw.rewrite("/files/a/"), "/files/a/");
+        assert_eq!(rw.rewrite("/files//b"), "/files//b");
+        assert_eq!(rw.rewrite("/other/a/b"), "/other/a/b");
+
+        let mut rw = Template::new("/", "/index.html").unwrap();
+        assert_eq!(rw.rewrite("/"), "/index.html");
+        assert_eq!(rw.rewrite("/a"), "/a");
+
+        let mut rw = Template::new("/{id}", "/static/{id}/{id}").unwrap();
+        assert_eq!(rw.rewrite("/a"), "/static/a/a");
+        assert_eq!(rw.rewrite("/a/b"), "/a/b");
+    }
+
+    #[test]
+    fn dot_segments() {
+        let mut rw = Template::new("/files/{dir}/{*path}", "/data/{dir}/{path}").unwrap();
+        assert_eq!(rw.rewrite("/files/../b"), "/files/../b");
+        assert_eq!(rw.rewrite("/files/%2E%2E/b"), "/files/%2E%2E/b");
+        assert_eq!(rw.rewrite("/files/a/b/%2e/c"), "/files/a/b/%2e/c");
+        assert_eq!(rw.rewrite("/files/a/b/../../.."), "/files/a/b/../../..");
+        assert_eq!(rw.rewrite("/files/a/.b/c.."), "/data/a/.b/c..");
+    }
+
+    #[test]
+    fn errors() {
+        let invalid = |s: &str| Err(TemplateError::InvalidCapture(s.to_owned()));
+        assert_eq!(Template::new("/a{id}", "/"), invalid("a{id}"));
+        assert_eq!(Template::new("/{id", "/"), invalid("{id"));
+        assert_eq!(Template::new("/{}", "/"), invalid("{}"));
+        assert_eq!(Template::new("/{id}/{id}", "/"), invalid("{id}"));
+        assert_eq!(Template::new("/{*rest}/a", "/"), invalid("a"));
+
+        let unbalanced = Err(TemplateError::UnbalancedBrace);
+        assert_eq!(Template::new("/{id}", "/{id"), unbalanced);
+        assert_eq!(Template::new("/{id}", "/id}"), unbalanced);
+        assert_eq!(Template::new("/{id}", "/{}"), unbalanced);
+        assert_eq!(
+            Template::new("/{id}", "/{name}"),
+            Err(TemplateError::UnknownPlaceholder("name".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Proxies Server-Sent Events and long polls, which stay open for a long time.
+//!
+//! [`Streaming`] wraps a service to bound the wait for the response by an idle timeout, which is
+//! what a long poll needs, and never the whole response. A total timeout, such as
+//! `tower::timeout`, would cut a healthy event stream, so use this instead.
+//!
+//! Only `text/event-stream` responses are treated as streams of events: the gaps between the
+//! frames of their body are bounded by the idle timeout as well, and `x-accel-buffering: no` and,
+//! if the upstream sets no `cache-control`, `cache-control: no-cache, no-transform` are added so
+//! that reverse proxies and CDNs in front do not buffer or transform the events either.
+//! Compression layers such as the one of `tower-http` skip `text/event-stream` by default. The
+//! body of the other responses is passed on as is, without idle timeout, so a slow download is
+//! not cut.
+//!
+//! When the client disconnects, the server drops the future or the body, which cancels the
+//! request to the upstream.
+//!
+//! ```no_run
+//! # #[cfg(feature = "axum")] {
+//! use std::time::Duration;
+//!
+//! use axum::Router;
+//! use axum_proxy::streaming::Streaming;
+//! use axum_proxy::Identity;
+//!
+//! # async fn run() {
+//! let events = axum_proxy::builder_http("backend:8080")
+//!     .unwrap()
+//!     .build(Identity);
+//! let svc = Streaming::new(events, Duration::from_secs(60));
+//! let app = Router::new().route_service("/events", svc);
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+//! axum::serve(listener, app).await.unwrap();
+//! # }
+//! # }
+//! ```
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use http::header::{HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use http::{Request, Response};
+use http_body::{Frame, SizeHint};
+use hyper::body::{Body as HttpBody, Incoming};
+use tokio::time::{Instant, Sleep};
+use tower_service::Service;
+
+use crate::Error;
+
+type BoxErr = Box<dyn std::error::Error + Send + Sync>;
+
+/// Returns whether `headers` are of a stream of Server-Sent Events.
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("text/event-stream"))
+}
+
+/// A [`Service<Request<B>>`] streaming the responses of the inner service with an idle timeout.
+///
+/// If the response does not arrive within the idle timeout, the result is [`Error::Timeout`]. If
+/// the body of a `text/event-stream` response stays idle for as long, it fails with
+/// [`Error::Timeout`]. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Streaming<S> {
+    inner: S,
+    idle_timeout: Duration,
+}
+
+impl<S> Streaming<S> {
+    pub fn new(inner: S, idle_timeout: Duration) -> Self {
+        Self {
+            inner,
+            idle_timeout,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B, RB> Service<Request<B>> for Streaming<S>
+where
+    S: Service<Request<B>, Response = Result<Response<RB>, Error>, Error = Infallible>,
+    S::Future: Unpin,
+{
+    type Response = Result<Response<StreamingBody<RB>>, Error>;
+    type Error = Infallible;
+    type Future = StreamingFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        StreamingFuture {
+            inner: Some(self.inner.call(req)),
+            idle: Box::pin(tokio::time::sleep(self.idle_timeout)),
+            idle_timeout: self.idle_timeout,
+        }
+    }
+}
+
+/// The future of [`Streaming`].
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct StreamingFuture<F> {
+    inner: Option<F>,
+    idle: Pin<Box<Sleep>>,
+    idle_timeout: Duration,
+}
+
+impl<F, B> Future for StreamingFuture<F>
+where
+    F: Future<Output = Result<Result<Response<B>, Error>, Infallible>> + Unpin,
+{
+    type Output = Result<Result<Response<StreamingBody<B>>, Error>, Infallible>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let Some(inner) = &mut this.inner else {
+            panic!("StreamingFuture::poll() is called after ready");
+        };
+        let Poll::Ready(Ok(result)) = Pin::new(inner).poll(cx) else {
+            ready!(this.idle.as_mut().poll(cx));
+            // Dropping the future cancels the request.
+            this.inner = None;
+            return Poll::Ready(Ok(Err(Error::Timeout)));
+        };
+        this.inner = None;
+
+        let response = result.map(|res| {
+            let (mut parts, inner) = res.into_parts();
+            let mut idle = None;
+            if is_event_stream(&parts.headers) {
+                parts
+                    .headers
+                    .insert("x-accel-buffering", HeaderValue::from_static("no"));
+                parts
+                    .headers
+                    .entry(CACHE_CONTROL)
+                    .or_insert(HeaderValue::from_static("no-cache, no-transform"));
+                idle = Some(Box::pin(tokio::time::sleep(this.idle_timeout)));
+            }
+
+            let body = StreamingBody {
+                inner,
+                idle,
+                idle_timeout: this.idle_timeout,
+            };
+            Response::from_parts(parts, body)
+        });
+        Poll::Ready(Ok(response))
+    }
+}
+
+/// The body of the responses from [`Streaming`], failing with [`Error::Timeout`] when the body of
+/// a `text/event-stream` response is idle.
+#[expect(clippy::module_name_repetitions)]
+#[derive(Debug)]
+pub struct StreamingBody<B = Incoming> {
+    inner: B,
+    /// Only set for `text/event-stream` responses.
+    idle: Option<Pin<Box<Sleep>>>,
+    idle_timeout: Duration,
+}
+
+impl<B> HttpBody for StreamingBody<B>
+where
+    B: HttpBody + Unpin,
+    B::Error: Into<BoxErr>,
+{
+    type Data = B::Data;
+    type Error = BoxErr;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(frame) => {
+                if let Some(idle) = &mut this.idle {
+                    idle.as_mut().reset(Instant::now() + this.idle_timeout);
+                }
+                Poll::Ready(frame.map(|f| f.map_err(Into::into)))
+            },
+            Poll::Pending => {
+                let Some(idle) = &mut this.idle else {
+                    return Poll::Pending;
+                };
+                ready!(idle.as_mut().poll(cx));
+                Poll::Ready(Some(Err(Error::Timeout.into())))
+            },
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http_body_util::BodyExt;
+    use hyper::body::Bytes;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::{mpsc, oneshot};
+
+    use super::*;
+    use crate::client::HttpConnector;
+    use crate::{builder_http, Identity, ReusedService};
+
+    type Svc = Streaming<ReusedService<Identity, HttpConnector, String>>;
+
+    /// A body made of the chunks received from a channel.
+    struct ChannelBody(mpsc::Receiver<&'static str>);
+
+    impl HttpBody for ChannelBody {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+            self.0.poll_recv(cx).map(|chunk| {
+                chunk.map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))))
+            })
+        }
+    }
+
+    /// Responds to one request with `content_type` after `head`, and a [`ChannelBody`] of
+    /// `chunks`, without any I/O so that the tests can pause the time.
+    struct Mock {
+        content_type: &'static str,
+        head: Option<oneshot::Receiver<()>>,
+        chunks: Option<mpsc::Receiver<&'static str>>,
+    }
+
+    impl Mock {
+        fn new(
+            content_type: &'static str,
+            head: oneshot::Receiver<()>,
+            chunks: mpsc::Receiver<&'static str>,
+        ) -> Self {
+            Self {
+                content_type,
+                head: Some(head),
+                chunks: Some(chunks),
+            }
+        }
+    }
+
+    impl Service<Request<()>> for Mock {
+        type Response = Result<Response<ChannelBody>, Error>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: Request<()>) -> Self::Future {
+            let content_type = self.content_type;
+            let head = self.head.take().unwrap();
+            let chunks = self.chunks.take().unwrap();
+            Box::pin(async move {
+                head.await.unwrap();
+                let response = Response::builder()
+                    .header(CONTENT_TYPE, content_type)
+                    .body(ChannelBody(chunks))
+                    .unwrap();
+                Ok(Ok(response))
+            })
+        }
+    }
+
+    /// Serves one event stream, writing the head after `head` and each event from `events`. Once
+    /// `events` is closed, reports when the connection is closed by the proxy.
+    async fn upstream(
+        head: oneshot::Receiver<()>,
+        mut events: mpsc::Receiver<&'static str>,
+        closed: oneshot::Sender<()>,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            head.await.unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+                      transfer-encoding: chunked\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            while let Some(event) = events.recv().await {
+                let chunk = format!("{:x}\r\n{event}\r\n", event.len());
+                stream.write_all(chunk.as_bytes()).await.unwrap();
+            }
+            let mut buf = [0; 1024];
+            while stream.read(&mut buf).await.is_ok_and(|n| n > 0) {}
+            let _ = closed.send(());
+        });
+        addr
+    }
+
+    fn make_svc(authority: String, idle_timeout: Duration) -> Svc {
+        let builder = builder_http(authority).unwrap();
+        Streaming::new(builder.build(Identity), idle_timeout)
+    }
+
+    #[tokio::test]
+    async fn event_stream() {
+        let (head, head_rx) = oneshot::channel();
+        let (events, events_rx) = mpsc::channel(1);
+        let (closed_tx, closed) = oneshot::channel();
+        let addr = upstream(head_rx, events_rx, closed_tx).await;
+
+        let mut svc = make_svc(addr, Duration::from_secs(5));
+        head.send(()).unwrap();
+        let request = Request::get("/events").body(String::new()).unwrap();
+        let response = svc.call(request).await.unwrap().unwrap();
+        assert_eq!(response.headers()["x-accel-buffering"], "no");
+        assert_eq!(
+            response.headers()["cache-control"],
+            "no-cache, no-transform"
+        );
+
+        let mut body = response.into_body();
+        for event in ["data: 1\n\n", "data: 2\n\n"] {
+            events.send(event).await.unwrap();
+            let frame = body.frame().await.unwrap().unwrap();
+            assert_eq!(frame.into_data().unwrap(), event);
+        }
+
+        // The upstream connection is closed when the client goes away.
+        drop(body);
+        drop(events);
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_timeout() {
+        let (head, head_rx) = oneshot::channel();
+        let (events, events_rx) = mpsc::channel(1);
+        let mut svc = Streaming::new(
+            Mock::new("text/event-stream", head_rx, events_rx),
+            Duration::from_millis(200),
+        );
+
+        let call = svc.call(Request::new(()));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        head.send(()).unwrap();
+        let response = call.await.unwrap().unwrap();
+
+        // Each event restarts the timer.
+        let mut body = response.into_body();
+        for event in ["data: 1\n\n", "data: 2\n\n", "data: 3\n\n"] {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            events.send(event).await.unwrap();
+            let frame = body.frame().await.unwrap().unwrap();
+            assert_eq!(frame.into_data().unwrap(), event);
+        }
+        let error = body.frame().await.unwrap().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(Error::Timeout)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_body() {
+        let (head, head_rx) = oneshot::channel();
+        let (chunks, chunks_rx) = mpsc::channel(1);
+        let mut svc = Streaming::new(
+            Mock::new("text/plain", head_rx, chunks_rx),
+            Duration::from_millis(200),
+        );
+
+        head.send(()).unwrap();
+        let response = svc.call(Request::new(())).await.unwrap().unwrap();
+        assert!(!response.headers().contains_key("x-accel-buffering"));
+
+        // The body of the other responses has no idle timeout.
+        let mut body = response.into_body();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        chunks.send("data").await.unwrap();
+        let frame = body.frame().await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "data");
+    }
+
+    #[tokio::test]
+    async fn no_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (closed_tx, closed) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            while stream.read(&mut buf).await.is_ok_and(|n| n > 0) {}
+            let _ = closed_tx.send(());
+        });
+
+        let mut svc = make_svc(addr, Duration::from_millis(100));
+        let request = Request::get("/poll").body(String::new()).unwrap();
+        let result = svc.call(request).await.unwrap();
+        assert!(matches!(result, Err(Error::Timeout)));
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
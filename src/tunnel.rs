@@ -0,0 +1,216 @@
+//! Handles `CONNECT host:port` requests, for an egress gateway.
+//!
+//! [`Tunnel`] checks the target against a [`HostPolicy`], dials it, responds `200` and then
+//! splices the upgraded client connection with the TCP stream. A denied target, or one resolving
+//! to a denied address, is [`Error::Forbidden`], and a failed dial is [`Error::Connect`].
+//!
+//! axum routes requests by path, which `CONNECT` requests do not have, so the requests must be
+//! dispatched by the method before the router:
+//!
+//! ```no_run
+//! # #[cfg(feature = "axum")] {
+//! use axum::extract::Request;
+//! use axum::response::IntoResponse;
+//! use axum::Router;
+//! use axum_proxy::policy::HostPolicy;
+//! use axum_proxy::tunnel::Tunnel;
+//! use http::Method;
+//! use tower::ServiceExt;
+//!
+//! # async fn run() {
+//! let tunnel = Tunnel::new(HostPolicy::new().allow("*.example.com", Some(443)));
+//! let router = Router::new();
+//!
+//! let app = tower::service_fn(move |req: Request| {
+//!     let tunnel = tunnel.clone();
+//!     let router = router.clone();
+//!     async move {
+//!         if req.method() == Method::CONNECT {
+//!             Ok(tunnel.oneshot(req).await.into_response())
+//!         } else {
+//!             router.oneshot(req).await
+//!         }
+//!     }
+//! });
+//!
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3128").await.unwrap();
+//! axum::serve(listener, tower::make::Shared::new(app)).await.unwrap();
+//! # }
+//! # }
+//! ```
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::{Method, Request, Response};
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpStream;
+use tower_service::Service;
+
+use crate::policy::HostPolicy;
+use crate::Error;
+
+/// A [`Service<Request<B>>`] tunnelling `CONNECT` requests to the allowed targets.
+///
+/// The connection must be served with upgrades enabled, as `axum::serve` does. Requests other
+/// than `CONNECT` with a `host:port` target are [`Error::Forbidden`].
+#[derive(Debug, Clone)]
+pub struct Tunnel {
+    policy: Arc<HostPolicy>,
+}
+
+impl Tunnel {
+    #[must_use]
+    pub fn new(policy: HostPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl<B> Service<Request<B>> for Tunnel
+where
+    B: Send + 'static,
+{
+    type Response = Result<Response<String>, Error>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let target = req
+            .uri()
+            .authority()
+            .filter(|_| req.method() == Method::CONNECT)
+            .and_then(|a| Some((a.host().to_owned(), a.port_u16()?)))
+            .filter(|(host, port)| self.policy.is_allowed(host, *port));
+        let Some((host, port)) = target else {
+            let error = Error::Forbidden(req.uri().clone());
+            return Box::pin(async { Ok(Err(error)) });
+        };
+
+        let policy = self.policy.clone();
+        let uri = req.uri().clone();
+        let on_upgrade = hyper::upgrade::on(&mut req);
+        Box::pin(async move {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let addrs: Vec<_> = match tokio::net::lookup_host((host, port)).await {
+                Ok(addrs) => addrs.collect(),
+                Err(e) => return Ok(Err(Error::Connect(e))),
+            };
+            if addrs
+                .iter()
+                .any(|addr| !policy.is_allowed_ip(addr.ip(), Some(port)))
+            {
+                return Ok(Err(Error::Forbidden(uri)));
+            }
+            let mut stream = match TcpStream::connect(&*addrs).await {
+                Ok(stream) => stream,
+                Err(e) => return Ok(Err(Error::Connect(e))),
+            };
+
+            tokio::spawn(async move {
+                let upgraded = match on_upgrade.await {
+                    Ok(upgraded) => upgraded,
+                    Err(e) => {
+                        log::error!("Upgrade failed: {e}");
+                        return;
+                    },
+                };
+                let mut upgraded = TokioIo::new(upgraded);
+                if let Err(e) = tokio::io::copy_bidirectional(&mut upgraded, &mut stream).await {
+                    log::debug!("Tunnel closed: {e}");
+                }
+            });
+
+            Ok(Ok(Response::new(String::new())))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    async fn serve(tunnel: Tunnel) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|req| {
+                let mut tunnel = tunnel.clone();
+                async move {
+                    let response = tunnel.call(req).await.unwrap();
+                    Ok::<_, Infallible>(response.unwrap_or_else(|e| {
+                        let mut response = Response::new(e.to_string());
+                        *response.status_mut() = http::StatusCode::FORBIDDEN;
+                        response
+                    }))
+                }
+            });
+            http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .with_upgrades()
+                .await
+                .unwrap();
+        });
+        addr
+    }
+
+    async fn connect(proxy: &str, target: &str) -> (TcpStream, String) {
+        let mut stream = TcpStream::connect(proxy).await.unwrap();
+        let request = format!("CONNECT {target} HTTP/1.1\r\nhost: {target}\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut buf = vec![0; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        (stream, String::from_utf8_lossy(&buf[..n]).into_owned())
+    }
+
+    #[tokio::test]
+    async fn splice() {
+        let echo = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = echo.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = echo.accept().await.unwrap();
+            let (mut read, mut write) = stream.split();
+            tokio::io::copy(&mut read, &mut write).await.unwrap();
+        });
+
+        let policy = HostPolicy::new().allow("127.0.0.1", Some(target.port()));
+        let proxy = serve(Tunnel::new(policy)).await;
+        let (mut stream, head) = connect(&proxy, &target.to_string()).await;
+        assert!(head.starts_with("HTTP/1.1 200"));
+
+        stream.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[tokio::test]
+    async fn forbidden() {
+        let proxy = serve(Tunnel::new(HostPolicy::new().allow("example.com", None))).await;
+        let (_stream, head) = connect(&proxy, "127.0.0.1:22").await;
+        assert!(head.starts_with("HTTP/1.1 403"));
+
+        // Resolves to a denied address.
+        let policy = HostPolicy::new()
+            .allow("*", None)
+            .deny("127.0.0.1", None)
+            .deny("::1", None);
+        let proxy = serve(Tunnel::new(policy)).await;
+        let (_stream, head) = connect(&proxy, "localhost:22").await;
+        assert!(head.starts_with("HTTP/1.1 403"));
+    }
+}